@@ -0,0 +1,545 @@
+// ban list and allowlist checked when clients are admitted
+// entries are keyed by account, session, client id or ip address
+// ban expiry is unix time, so temporary bans survive restarts
+// file backed lists are saved on every change and reloaded by command
+// when the allowlist is not empty only clients matching it are admitted
+
+use std::{
+    fmt,
+    fs,
+    io::ErrorKind,
+    net::IpAddr,
+    path::PathBuf,
+    str::FromStr,
+    time::{Duration, SystemTime}
+};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet, Uuid}
+};
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::renet::{
+    self,
+    RenetServer,
+    transport::NetcodeServerTransport
+};
+use crate::{
+    *,
+    admin_console::*,
+    auth::AccountId,
+    user_data::ConnectionUserData
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AccessKey {
+    Account(AccountId),
+    Session(Uuid),
+    Client(ClientId),
+    Ip(IpAddr)
+}
+
+// account:<id>, session:<uuid>, client:<id>, ip:<addr>
+impl fmt::Display for AccessKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccessKey::Account(account) => write!(f, "account:{}", account.0),
+            AccessKey::Session(session) => write!(f, "session:{session}"),
+            AccessKey::Client(client_id) => write!(f, "client:{}", client_id.get()),
+            AccessKey::Ip(ip) => write!(f, "ip:{ip}")
+        }
+    }
+}
+
+impl FromStr for AccessKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let Some((kind, value)) = s.split_once(':') else {
+            anyhow::bail!("expected <kind>:<value>, got: {s}");
+        };
+        Ok(match kind {
+            "account" => AccessKey::Account(AccountId(value.parse()?)),
+            "session" => AccessKey::Session(Uuid::parse_str(value)?),
+            "client" => AccessKey::Client(ClientId::new(value.parse()?)),
+            "ip" => AccessKey::Ip(value.parse()?),
+            _ => anyhow::bail!("unknown key kind: {kind}")
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Ban {
+    pub key: AccessKey,
+    // unix seconds, None is permanent
+    pub until: Option<u64>,
+    pub reason: String
+}
+
+impl Ban {
+    #[inline]
+    pub fn is_active(&self, now: u64) -> bool {
+        self.until.is_none_or(|until| now < until)
+    }
+}
+
+#[inline]
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .map_or(0, |d| d.as_secs())
+}
+
+// every key a connecting client can be matched by
+pub fn connection_keys(
+    client_id: ClientId,
+    account_id: Option<AccountId>,
+    user_data: &ConnectionUserData,
+    transport: Option<&NetcodeServerTransport>
+) -> Vec<AccessKey> {
+    let mut keys = vec![AccessKey::Client(client_id)];
+    keys.extend(account_id.map(AccessKey::Account));
+    keys.extend(user_data.get(client_id)
+        .map(|u| AccessKey::Session(u.session_id()))
+    );
+    keys.extend(transport
+        .and_then(|t| t.client_addr(renet::ClientId::from_raw(client_id.get())))
+        .map(|addr| AccessKey::Ip(addr.ip()))
+    );
+    keys
+}
+
+// in memory unless loaded from a file
+#[derive(Resource, Default)]
+pub struct AccessList {
+    bans: HashMap<AccessKey, Ban>,
+    allowed: HashSet<AccessKey>,
+    path: Option<PathBuf>
+}
+
+impl AccessList {
+    // missing file is an empty list, created on first change
+    pub fn load(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let mut list = Self {
+            path: Some(path.into()),
+            ..default()
+        };
+        list.reload()?;
+        Ok(list)
+    }
+
+    // keeps current entries when the file is broken
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into())
+        };
+
+        let (bans, allowed) = parse_access_list(&text)?;
+        self.bans = bans;
+        self.allowed = allowed;
+        Ok(())
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    // one entry per line, sorted so diffs stay readable
+    // ban <key> <until|-> [reason]
+    // allow <key>
+    pub fn to_text(&self) -> String {
+        let mut bans = self.bans.values()
+        .map(|ban| {
+            let until = ban.until.map_or("-".to_string(), |u| u.to_string());
+            format!("ban {} {until} {}", ban.key, ban.reason).trim_end().to_string()
+        })
+        .collect::<Vec<_>>();
+        bans.sort();
+        let mut allowed = self.allowed.iter()
+        .map(|key| format!("allow {key}"))
+        .collect::<Vec<_>>();
+        allowed.sort();
+
+        bans.into_iter()
+        .chain(allowed)
+        .map(|line| line + "\n")
+        .collect()
+    }
+
+    // replaces an earlier ban of the same key
+    pub fn ban(&mut self, key: AccessKey, duration: Option<Duration>, reason: impl Into<String>) {
+        let until = duration.map(|d| unix_now().saturating_add(d.as_secs()));
+        self.bans.insert(key, Ban {
+            key,
+            until,
+            reason: reason.into()
+        });
+    }
+
+    #[inline]
+    pub fn unban(&mut self, key: &AccessKey) -> bool {
+        self.bans.remove(key).is_some()
+    }
+
+    #[inline]
+    pub fn allow(&mut self, key: AccessKey) -> bool {
+        self.allowed.insert(key)
+    }
+
+    #[inline]
+    pub fn disallow(&mut self, key: &AccessKey) -> bool {
+        self.allowed.remove(key)
+    }
+
+    #[inline]
+    pub fn bans(&self) -> impl Iterator<Item = &Ban> {
+        self.bans.values()
+    }
+
+    #[inline]
+    pub fn allowed(&self) -> impl Iterator<Item = &AccessKey> {
+        self.allowed.iter()
+    }
+
+    // returns the number of expired bans removed
+    pub fn prune(&mut self, now: u64) -> usize {
+        let before = self.bans.len();
+        self.bans.retain(|_, ban| ban.is_active(now));
+        before - self.bans.len()
+    }
+
+    // any active ban rejects, then the allowlist when it is not empty
+    pub fn check(&self, keys: &[AccessKey], now: u64) -> Result<(), String> {
+        if let Some(ban) = keys.iter()
+        .filter_map(|key| self.bans.get(key))
+        .find(|ban| ban.is_active(now)) {
+            return Err(format!("banned as {}: {}", ban.key, ban.reason));
+        }
+        if !self.allowed.is_empty() && !keys.iter().any(|key| self.allowed.contains(key)) {
+            return Err("not on allowlist".to_string());
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_access_list(text: &str) -> anyhow::Result<(HashMap<AccessKey, Ban>, HashSet<AccessKey>)> {
+    let mut bans = HashMap::new();
+    let mut allowed = HashSet::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let kind = words.next().unwrap_or_default();
+        let key = words.next()
+        .ok_or_else(|| anyhow::anyhow!("line {}: missing key", i + 1))?
+        .parse::<AccessKey>()
+        .map_err(|e| anyhow::anyhow!("line {}: {e}", i + 1))?;
+
+        match kind {
+            "ban" => {
+                let until = match words.next() {
+                    None | Some("-") => None,
+                    Some(until) => Some(until.parse::<u64>()
+                        .map_err(|e| anyhow::anyhow!("line {}: {e}", i + 1))?
+                    )
+                };
+                let reason = words.collect::<Vec<_>>().join(" ");
+                bans.insert(key, Ban { key, until, reason });
+            }
+            "allow" => {
+                allowed.insert(key);
+            }
+            _ => anyhow::bail!("line {}: unknown entry: {kind}", i + 1)
+        }
+    }
+    Ok((bans, allowed))
+}
+
+pub struct AccessListPlugin;
+
+impl Plugin for AccessListPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AccessList>()
+        // commands work with or without a console
+        .add_event::<AdminCommand>()
+        .add_admin_command("ban", "ban <key> [minutes] [reason], key is account:<id> session:<uuid> client:<id> ip:<addr>")
+        .add_admin_command("unban", "unban <key>")
+        .add_admin_command("allow", "allow <key>")
+        .add_admin_command("disallow", "disallow <key>")
+        .add_admin_command("access-list", "access-list")
+        .add_admin_command("reload-access-list", "reload-access-list")
+        .add_systems(Update, (
+            ban_command,
+            allow_command,
+            access_list_command
+        ));
+    }
+}
+
+#[inline]
+fn parse_key(cmd: &AdminCommand, registry: &AdminCommands) -> Option<AccessKey> {
+    match cmd.args.first().map(|k| k.parse::<AccessKey>()) {
+        Some(Ok(key)) => Some(key),
+        Some(Err(e)) => {
+            cmd.reply.send(format!("invalid key: {e}"));
+            None
+        }
+        None => {
+            let usage = registry.usage(&cmd.name).unwrap_or_default();
+            cmd.reply.send(format!("usage: {usage}"));
+            None
+        }
+    }
+}
+
+#[inline]
+fn save_access_list(access: &AccessList, cmd: &AdminCommand) {
+    if let Err(e) = access.save() {
+        cmd.reply.send(format!("failed to save access list: {e}"));
+    }
+}
+
+fn ban_command(
+    mut commands: EventReader<AdminCommand>,
+    registry: Res<AdminCommands>,
+    mut access: ResMut<AccessList>,
+    user_data: Res<ConnectionUserData>,
+    characters: Query<(&NetworkId, Option<&AccountId>)>,
+    transport: Option<Res<NetcodeServerTransport>>,
+    mut renet: Option<ResMut<RenetServer>>
+) {
+    for cmd in commands.read() {
+        if cmd.is("unban") {
+            let Some(key) = parse_key(cmd, &registry) else {
+                continue;
+            };
+            if access.unban(&key) {
+                save_access_list(&access, cmd);
+                cmd.reply.send(format!("{key} unbanned"));
+            } else {
+                cmd.reply.send(format!("{key} is not banned"));
+            }
+            continue;
+        }
+        if !cmd.is("ban") {
+            continue;
+        }
+
+        let Some(key) = parse_key(cmd, &registry) else {
+            continue;
+        };
+        // minutes are optional, anything else is the reason
+        let minutes = cmd.arg::<u64>(1);
+        let reason = cmd.args.iter()
+        .skip(if minutes.is_some() { 2 } else { 1 })
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(" ");
+        access.ban(key, minutes.map(|m| Duration::from_secs(m.saturating_mul(60))), reason);
+        save_access_list(&access, cmd);
+        match minutes {
+            Some(m) => cmd.reply.send(format!("{key} banned for {m} minutes")),
+            None => cmd.reply.send(format!("{key} banned"))
+        }
+
+        // clients already in game are kicked
+        let Some(renet) = renet.as_deref_mut() else {
+            continue;
+        };
+        for (net_id, account_id) in characters.iter() {
+            let client_id = net_id.client_id();
+            let keys = connection_keys(
+                client_id,
+                account_id.copied(),
+                &user_data,
+                transport.as_deref()
+            );
+            if keys.contains(&key) {
+                renet.disconnect(renet::ClientId::from_raw(client_id.get()));
+                cmd.reply.send(format!("client: {} kicked", client_id.get()));
+            }
+        }
+    }
+}
+
+// allowlist changes do not kick anyone
+fn allow_command(
+    mut commands: EventReader<AdminCommand>,
+    registry: Res<AdminCommands>,
+    mut access: ResMut<AccessList>
+) {
+    for cmd in commands.read() {
+        if cmd.is("allow") {
+            let Some(key) = parse_key(cmd, &registry) else {
+                continue;
+            };
+            access.allow(key);
+            save_access_list(&access, cmd);
+            cmd.reply.send(format!("{key} allowed"));
+        } else if cmd.is("disallow") {
+            let Some(key) = parse_key(cmd, &registry) else {
+                continue;
+            };
+            if access.disallow(&key) {
+                save_access_list(&access, cmd);
+                cmd.reply.send(format!("{key} removed from allowlist"));
+            } else {
+                cmd.reply.send(format!("{key} is not on allowlist"));
+            }
+        }
+    }
+}
+
+fn access_list_command(
+    mut commands: EventReader<AdminCommand>,
+    mut access: ResMut<AccessList>
+) {
+    for cmd in commands.read() {
+        if cmd.is("reload-access-list") {
+            match access.reload() {
+                Ok(()) => cmd.reply.send("access list reloaded"),
+                Err(e) => cmd.reply.send(format!("failed to reload access list: {e}"))
+            }
+            continue;
+        }
+        if !cmd.is("access-list") {
+            continue;
+        }
+
+        let now = unix_now();
+        if access.prune(now) > 0 {
+            save_access_list(&access, cmd);
+        }
+        for line in access.to_text().lines() {
+            cmd.reply.send(line.to_string());
+        }
+        for ban in access.bans().filter(|b| b.until.is_some()) {
+            let left = ban.until.unwrap_or_default().saturating_sub(now);
+            cmd.reply.send(format!("{} expires in {}m", ban.key, left.div_ceil(60)));
+        }
+        if access.allowed().next().is_none() {
+            cmd.reply.send("allowlist is empty, everyone not banned is admitted");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn account(id: u64) -> AccessKey {
+        AccessKey::Account(AccountId(id))
+    }
+
+    fn list(text: &str) -> AccessList {
+        let (bans, allowed) = parse_access_list(text).unwrap();
+        AccessList {
+            bans,
+            allowed,
+            path: None
+        }
+    }
+
+    #[test]
+    fn parses_entries() {
+        let text = format!(
+            "# access\n\nban account:1 - cheating in room 2\nban ip:10.0.0.1 {}\n  allow client:5\nallow session:{}\n",
+            NOW + 60,
+            Uuid::nil()
+        );
+        let (bans, allowed) = parse_access_list(&text).unwrap();
+        assert_eq!(bans.len(), 2);
+        let ban = &bans[&account(1)];
+        assert_eq!(ban.until, None);
+        assert_eq!(ban.reason, "cheating in room 2");
+        let ban = &bans[&AccessKey::Ip("10.0.0.1".parse().unwrap())];
+        assert_eq!(ban.until, Some(NOW + 60));
+        assert!(ban.reason.is_empty());
+        assert!(allowed.contains(&AccessKey::Client(ClientId::new(5))));
+        assert!(allowed.contains(&AccessKey::Session(Uuid::nil())));
+    }
+
+    #[test]
+    fn round_trips_text() {
+        let text = "ban account:1 - cheating\nban client:3 1700000060\nallow ip:::1\n";
+        assert_eq!(list(text).to_text(), text);
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        for text in [
+            "ban",
+            "kick account:1",
+            "ban account",
+            "ban user:1",
+            "ban account:x",
+            "ban ip:300.0.0.1",
+            "ban account:1 soon",
+            "allow session:not-a-uuid"
+        ] {
+            assert!(parse_access_list(text).is_err(), "accepted: {text:?}");
+        }
+        let e = parse_access_list("allow account:1\n\nban").unwrap_err();
+        assert!(e.to_string().starts_with("line 3:"), "{e}");
+    }
+
+    #[test]
+    fn temporary_ban_expires() {
+        let list = list(&format!("ban account:1 {} spam", NOW + 60));
+        let keys = [AccessKey::Client(ClientId::new(9)), account(1)];
+        let e = list.check(&keys, NOW).unwrap_err();
+        assert_eq!(e, "banned as account:1: spam");
+        assert!(list.check(&keys, NOW + 59).is_err());
+        assert!(list.check(&keys, NOW + 60).is_ok());
+    }
+
+    #[test]
+    fn permanent_ban_never_expires() {
+        let list = list("ban account:1 -");
+        assert!(list.check(&[account(1)], NOW).is_err());
+        assert!(list.check(&[account(1)], u64::MAX).is_err());
+        assert!(list.check(&[account(2)], NOW).is_ok());
+    }
+
+    #[test]
+    fn allowlist_admits_only_listed() {
+        let mut list = list("allow account:1");
+        assert!(list.check(&[account(1)], NOW).is_ok());
+        assert!(list.check(&[AccessKey::Client(ClientId::new(1)), account(1)], NOW).is_ok());
+        assert_eq!(list.check(&[account(2)], NOW).unwrap_err(), "not on allowlist");
+        // a ban wins over the allowlist
+        list.ban(account(1), None, "");
+        assert!(list.check(&[account(1)], NOW).is_err());
+        assert!(list.disallow(&account(1)));
+        assert!(list.unban(&account(1)));
+        assert!(list.check(&[account(2)], NOW).is_ok());
+    }
+
+    #[test]
+    fn prunes_expired_bans() {
+        let mut list = list(&format!("ban account:1 {}\nban account:2 -\nban account:3 {}", NOW, NOW + 1));
+        assert_eq!(list.prune(NOW), 1);
+        assert_eq!(list.bans().count(), 2);
+        assert_eq!(list.prune(NOW + 1), 1);
+        assert_eq!(list.bans().next().unwrap().key, account(2));
+    }
+
+    #[test]
+    fn huge_ban_duration_saturates() {
+        let mut list = AccessList::default();
+        list.ban(account(1), Some(Duration::MAX), "");
+        assert_eq!(list.bans().next().unwrap().until, Some(u64::MAX));
+    }
+}
@@ -0,0 +1,512 @@
+// admin commands from stdin and an optional local tcp socket
+// each line is dispatched as AdminCommand event,
+// game code registers its own commands with add_admin_command
+// and reads AdminCommand events filtered by name
+// commands that change simulation only send events,
+// AdminSimulationPlugin applies them so the recorder and replay see them
+
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex
+    },
+    thread,
+    time::Duration
+};
+use bevy::{
+    prelude::*,
+    app::AppExit
+};
+use serde::{Serialize, Deserialize};
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::renet::{
+    self,
+    RenetServer,
+    transport::NetcodeServerTransport
+};
+use bevy_xpbd_3d::prelude::*;
+use crate::{
+    *,
+    room::RoomId,
+    character_controller::FallTracker
+};
+
+// reply to whoever issued the command
+// stdin commands only reply to the log
+#[derive(Clone, Default)]
+pub struct AdminReply(Option<Sender<String>>);
+
+impl AdminReply {
+    pub fn send(&self, msg: impl Into<String>) {
+        let msg = msg.into();
+        info!("admin: {msg}");
+        if let Some(tx) = &self.0 {
+            // socket may already be closed
+            let _ = tx.send(msg);
+        }
+    }
+}
+
+#[derive(Event, Clone)]
+pub struct AdminCommand {
+    pub name: String,
+    pub args: Vec<String>,
+    pub reply: AdminReply
+}
+
+impl AdminCommand {
+    #[inline]
+    pub fn is(&self, name: &str) -> bool {
+        self.name == name
+    }
+
+    #[inline]
+    pub fn arg<T: std::str::FromStr>(&self, index: usize) -> Option<T> {
+        self.args.get(index)?.parse().ok()
+    }
+}
+
+// registered command names with usage, unknown commands are rejected
+#[derive(Resource, Default)]
+pub struct AdminCommands(BTreeMap<String, String>);
+
+impl AdminCommands {
+    #[inline]
+    pub fn register(&mut self, name: impl Into<String>, usage: impl Into<String>) {
+        self.0.insert(name.into(), usage.into());
+    }
+
+    #[inline]
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    #[inline]
+    pub fn usage(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+}
+
+pub trait AdminCommandAppExt {
+    fn add_admin_command(&mut self, name: &str, usage: &str) -> &mut Self;
+}
+
+impl AdminCommandAppExt for App {
+    fn add_admin_command(&mut self, name: &str, usage: &str) -> &mut Self {
+        self.world.get_resource_or_insert_with(AdminCommands::default)
+        .register(name, usage);
+        self
+    }
+}
+
+// admin changes to simulation, recorded and replayed like room events
+#[derive(Event, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct TeleportCharacter {
+    pub client_id: ClientId,
+    pub position: Vec3
+}
+
+// fixed update and physics step at the same rate
+#[derive(Event, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SetTickRate(pub f64);
+
+// virtual time drives fixed update, network keeps running while paused
+#[derive(Event, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SetPaused(pub bool);
+
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct AdminSimulationSet;
+
+// applied at the end of the next tick, so a replay
+// changes its time step between the same two ticks
+#[derive(Resource, Default)]
+struct PendingTickRate(Option<f64>);
+
+// part of the server simulation, replay has it without a console
+pub struct AdminSimulationPlugin;
+
+impl Plugin for AdminSimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingTickRate>()
+        .add_event::<TeleportCharacter>()
+        .add_event::<SetTickRate>()
+        .add_event::<SetPaused>()
+        .add_systems(PreUpdate, (
+            apply_teleport,
+            queue_tick_rate,
+            apply_pause
+        ).in_set(AdminSimulationSet
+        ).after(ServerSet::Receive))
+        .add_systems(FixedUpdate,
+            apply_tick_rate
+            .after(AFTER_PHYSICS_SET)
+        );
+    }
+}
+
+fn apply_teleport(
+    mut events: EventReader<TeleportCharacter>,
+    mut query: Query<(
+        &NetworkId,
+        &mut Position,
+        &mut LinearVelocity,
+        &mut FallTracker
+    )>
+) {
+    for TeleportCharacter { client_id, position } in events.read() {
+        let Some((_, mut pos, mut vel, mut fall)) = query.iter_mut()
+        .find(|(net_id, ..)| net_id.client_id() == *client_id) else {
+            continue;
+        };
+        // Position only, physics adds a written Transform on top of it
+        pos.0 = *position;
+        vel.0 = Vec3::ZERO;
+        fall.reset();
+    }
+}
+
+fn queue_tick_rate(
+    mut events: EventReader<SetTickRate>,
+    mut pending: ResMut<PendingTickRate>
+) {
+    if let Some(SetTickRate(hz)) = events.read().last() {
+        pending.0 = Some(*hz);
+    }
+}
+
+fn apply_tick_rate(
+    mut pending: ResMut<PendingTickRate>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+    mut physics_time: ResMut<Time<Physics>>
+) {
+    let Some(hz) = pending.0.take() else {
+        return;
+    };
+
+    // one physics step per fixed update
+    let delta = Duration::from_secs_f64(1.0 / hz);
+    fixed_time.set_timestep(delta);
+    if let TimestepMode::FixedOnce { delta: physics_delta } = physics_time.timestep_mode_mut() {
+        *physics_delta = delta;
+    }
+}
+
+fn apply_pause(
+    mut events: EventReader<SetPaused>,
+    mut virtual_time: ResMut<Time<Virtual>>
+) {
+    for SetPaused(paused) in events.read() {
+        if *paused {
+            virtual_time.pause();
+        } else {
+            virtual_time.unpause();
+        }
+    }
+}
+
+struct AdminLine {
+    line: String,
+    reply: AdminReply
+}
+
+// lines from reader threads
+#[derive(Resource)]
+struct AdminInput(Mutex<Receiver<AdminLine>>);
+
+pub struct AdminConsolePlugin {
+    pub stdin: bool,
+    // keep this on loopback, there is no authentication
+    pub listen_addr: Option<SocketAddr>
+}
+
+impl Default for AdminConsolePlugin {
+    fn default() -> Self {
+        Self {
+            stdin: true,
+            listen_addr: None
+        }
+    }
+}
+
+impl Plugin for AdminConsolePlugin {
+    fn build(&self, app: &mut App) {
+        let (tx, rx) = mpsc::channel();
+        if self.stdin {
+            spawn_stdin_reader(tx.clone());
+        }
+        if let Some(addr) = self.listen_addr {
+            match TcpListener::bind(addr) {
+                Ok(listener) => {
+                    info!("admin console listening on: {addr}");
+                    spawn_tcp_listener(listener, tx);
+                }
+                Err(e) => error!("failed to bind admin console: {e}")
+            }
+        }
+
+        app.insert_resource(AdminInput(Mutex::new(rx)))
+        .init_resource::<AdminCommands>()
+        .add_event::<AdminCommand>()
+        .add_admin_command("help", "help")
+        .add_admin_command("list", "list")
+        .add_admin_command("kick", "kick <client>")
+        .add_admin_command("teleport", "teleport <client> <x> <y> <z>")
+        .add_admin_command("set-tick-rate", "set-tick-rate <hz>")
+        .add_admin_command("pause", "pause")
+        .add_admin_command("resume", "resume")
+        .add_admin_command("shutdown", "shutdown")
+        .add_systems(PreUpdate, dispatch_admin_command)
+        .add_systems(Update, (
+            help_command,
+            list_command,
+            kick_command,
+            teleport_command,
+            tick_rate_command,
+            pause_command,
+            shutdown_command
+        ));
+    }
+}
+
+fn spawn_stdin_reader(tx: Sender<AdminLine>) {
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let line = AdminLine { line, reply: AdminReply::default() };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn spawn_tcp_listener(listener: TcpListener, tx: Sender<AdminLine>) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let tx = tx.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = handle_tcp_session(stream, tx) {
+                            warn!("admin session closed: {e}");
+                        }
+                    });
+                }
+                Err(e) => warn!("failed to accept admin session: {e}")
+            }
+        }
+    });
+}
+
+fn handle_tcp_session(stream: TcpStream, tx: Sender<AdminLine>) -> anyhow::Result<()> {
+    let peer = stream.peer_addr()?;
+    info!("admin session opened from: {peer}");
+
+    // writer lives until every reply sender is dropped
+    let mut writer = stream.try_clone()?;
+    let (reply_tx, reply_rx) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        for msg in reply_rx {
+            if writeln!(writer, "{msg}").is_err() {
+                break;
+            }
+        }
+    });
+
+    for line in BufReader::new(stream).lines() {
+        let line = AdminLine {
+            line: line?,
+            reply: AdminReply(Some(reply_tx.clone()))
+        };
+        if tx.send(line).is_err() {
+            break;
+        }
+    }
+    info!("admin session closed from: {peer}");
+    Ok(())
+}
+
+fn dispatch_admin_command(
+    input: Res<AdminInput>,
+    registry: Res<AdminCommands>,
+    mut commands: EventWriter<AdminCommand>
+) {
+    let Ok(rx) = input.0.lock() else {
+        return;
+    };
+
+    for AdminLine { line, reply } in rx.try_iter() {
+        let mut words = line.split_whitespace().map(str::to_string);
+        let Some(name) = words.next() else {
+            continue;
+        };
+        if !registry.contains(&name) {
+            reply.send(format!("unknown command: {name}, try help"));
+            continue;
+        }
+
+        commands.send(AdminCommand {
+            name,
+            args: words.collect(),
+            reply
+        });
+    }
+}
+
+#[inline]
+fn parse_client_id(cmd: &AdminCommand) -> Option<ClientId> {
+    cmd.arg::<u64>(0).map(ClientId::new)
+}
+
+#[inline]
+fn reply_usage(cmd: &AdminCommand, registry: &AdminCommands) {
+    let usage = registry.usage(&cmd.name).unwrap_or_default();
+    cmd.reply.send(format!("usage: {usage}"));
+}
+
+fn help_command(
+    mut commands: EventReader<AdminCommand>,
+    registry: Res<AdminCommands>
+) {
+    for cmd in commands.read().filter(|c| c.is("help")) {
+        for usage in registry.0.values() {
+            cmd.reply.send(usage.clone());
+        }
+    }
+}
+
+fn list_command(
+    mut commands: EventReader<AdminCommand>,
+    query: Query<(&NetworkId, &Transform, Option<&RoomId>)>,
+    renet: Option<Res<RenetServer>>
+) {
+    for cmd in commands.read().filter(|c| c.is("list")) {
+        cmd.reply.send(format!("{} clients", query.iter().len()));
+        for (net_id, transform, room) in query.iter() {
+            let client_id = net_id.client_id();
+            let rtt = renet.as_ref()
+            .and_then(|r| r.network_info(renet::ClientId::from_raw(client_id.get())).ok())
+            .map_or(0.0, |info| info.rtt);
+            cmd.reply.send(format!(
+                "client: {} room: {:?} position: {} rtt: {rtt:.1}ms",
+                client_id.get(),
+                room,
+                transform.translation
+            ));
+        }
+    }
+}
+
+fn kick_command(
+    mut commands: EventReader<AdminCommand>,
+    registry: Res<AdminCommands>,
+    renet: Option<ResMut<RenetServer>>
+) {
+    let Some(mut renet) = renet else {
+        return;
+    };
+
+    for cmd in commands.read().filter(|c| c.is("kick")) {
+        let Some(client_id) = parse_client_id(cmd) else {
+            reply_usage(cmd, &registry);
+            continue;
+        };
+        let renet_id = renet::ClientId::from_raw(client_id.get());
+        if !renet.is_connected(renet_id) {
+            cmd.reply.send(format!("client: {} is not connected", client_id.get()));
+            continue;
+        }
+
+        renet.disconnect(renet_id);
+        cmd.reply.send(format!("client: {} kicked", client_id.get()));
+    }
+}
+
+fn teleport_command(
+    mut commands: EventReader<AdminCommand>,
+    registry: Res<AdminCommands>,
+    query: Query<&NetworkId>,
+    mut teleports: EventWriter<TeleportCharacter>
+) {
+    for cmd in commands.read().filter(|c| c.is("teleport")) {
+        let (Some(client_id), Some(x), Some(y), Some(z)) = (
+            parse_client_id(cmd),
+            cmd.arg::<f32>(1),
+            cmd.arg::<f32>(2),
+            cmd.arg::<f32>(3)
+        ) else {
+            reply_usage(cmd, &registry);
+            continue;
+        };
+        let target = Vec3::new(x, y, z);
+        if !target.is_finite() {
+            reply_usage(cmd, &registry);
+            continue;
+        }
+
+        if !query.iter().any(|net_id| net_id.client_id() == client_id) {
+            cmd.reply.send(format!("client: {} has no character", client_id.get()));
+            continue;
+        }
+        teleports.send(TeleportCharacter {
+            client_id,
+            position: target
+        });
+        cmd.reply.send(format!("client: {} teleported to {target}", client_id.get()));
+    }
+}
+
+fn tick_rate_command(
+    mut commands: EventReader<AdminCommand>,
+    registry: Res<AdminCommands>,
+    mut tick_rates: EventWriter<SetTickRate>
+) {
+    for cmd in commands.read().filter(|c| c.is("set-tick-rate")) {
+        let Some(hz) = cmd.arg::<f64>(0)
+        .filter(|hz| hz.is_finite() && *hz > 0.0) else {
+            reply_usage(cmd, &registry);
+            continue;
+        };
+
+        tick_rates.send(SetTickRate(hz));
+        cmd.reply.send(format!("tick rate set to {hz}hz"));
+    }
+}
+
+fn pause_command(
+    mut commands: EventReader<AdminCommand>,
+    mut pauses: EventWriter<SetPaused>
+) {
+    for cmd in commands.read() {
+        if cmd.is("pause") {
+            pauses.send(SetPaused(true));
+            cmd.reply.send("simulation paused");
+        } else if cmd.is("resume") {
+            pauses.send(SetPaused(false));
+            cmd.reply.send("simulation resumed");
+        }
+    }
+}
+
+fn shutdown_command(
+    mut commands: EventReader<AdminCommand>,
+    mut exit: EventWriter<AppExit>,
+    transport: Option<ResMut<NetcodeServerTransport>>,
+    renet: Option<ResMut<RenetServer>>
+) {
+    if !commands.read().any(|c| c.is("shutdown")) {
+        return;
+    }
+
+    // tell clients now rather than letting them time out
+    if let (Some(mut transport), Some(mut renet)) = (transport, renet) {
+        transport.disconnect_all(&mut renet);
+    }
+    info!("admin: shutting down");
+    exit.send(AppExit);
+}
@@ -0,0 +1,408 @@
+// session validation at connection
+// the session id in netcode user data is checked by a pluggable Authenticator
+// on the io task pool, characters are spawned on ClientAuthenticated
+// and clients that fail are disconnected
+// without Authentication resource every client is accepted, replay relies on this
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::Duration
+};
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, IoTaskPool, Task},
+    utils::{HashMap, Uuid}
+};
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::renet::{self, RenetServer};
+use crate::user_data::ConnectionUserData;
+
+pub const AUTH_HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+// plenty for a status line, a few headers and an account id
+const AUTH_HTTP_MAX_RESPONSE: u64 = 4096;
+
+// server side only, attached to the character
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct AccountId(pub u64);
+
+// runs on the io task pool, blocking is fine
+pub trait Authenticator: Send + Sync + 'static {
+    fn authenticate(&self, session_id: Uuid) -> anyhow::Result<AccountId>;
+}
+
+#[derive(Default, Clone)]
+pub struct InMemoryAuthenticator {
+    sessions: HashMap<Uuid, AccountId>
+}
+
+impl InMemoryAuthenticator {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn with_session(mut self, session_id: Uuid, account_id: AccountId) -> Self {
+        self.insert(session_id, account_id);
+        self
+    }
+
+    #[inline]
+    pub fn insert(&mut self, session_id: Uuid, account_id: AccountId) {
+        self.sessions.insert(session_id, account_id);
+    }
+}
+
+impl Authenticator for InMemoryAuthenticator {
+    fn authenticate(&self, session_id: Uuid) -> anyhow::Result<AccountId> {
+        self.sessions.get(&session_id)
+        .copied()
+        .ok_or_else(|| anyhow::anyhow!("unknown session"))
+    }
+}
+
+// one "<session uuid> <account id>" per line, # starts a comment
+// read on every authentication, so sessions can be added while running
+#[derive(Clone)]
+pub struct FileAuthenticator {
+    path: PathBuf
+}
+
+impl FileAuthenticator {
+    #[inline]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+pub fn parse_sessions(text: &str) -> anyhow::Result<HashMap<Uuid, AccountId>> {
+    let mut sessions = HashMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let (Some(session), Some(account), None) = (words.next(), words.next(), words.next()) else {
+            anyhow::bail!("line {}: expected <session> <account>", i + 1);
+        };
+        let session = Uuid::parse_str(session)
+        .map_err(|e| anyhow::anyhow!("line {}: {e}", i + 1))?;
+        let account = account.parse::<u64>()
+        .map_err(|e| anyhow::anyhow!("line {}: {e}", i + 1))?;
+        sessions.insert(session, AccountId(account));
+    }
+    Ok(sessions)
+}
+
+impl Authenticator for FileAuthenticator {
+    fn authenticate(&self, session_id: Uuid) -> anyhow::Result<AccountId> {
+        let text = fs::read_to_string(&self.path)?;
+        parse_sessions(&text)?
+        .get(&session_id)
+        .copied()
+        .ok_or_else(|| anyhow::anyhow!("unknown session"))
+    }
+}
+
+// GET /sessions/<uuid>, 200 with the account id as plain text body accepts
+// minimal http/1.0 like metrics, no tls, keep the service on loopback
+#[derive(Clone)]
+pub struct HttpAuthenticator {
+    addr: SocketAddr,
+    timeout: Duration
+}
+
+impl HttpAuthenticator {
+    #[inline]
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            timeout: AUTH_HTTP_TIMEOUT
+        }
+    }
+
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl Authenticator for HttpAuthenticator {
+    fn authenticate(&self, session_id: Uuid) -> anyhow::Result<AccountId> {
+        let mut stream = TcpStream::connect_timeout(&self.addr, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        write!(
+            stream,
+            "GET /sessions/{session_id} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            self.addr
+        )?;
+        stream.flush()?;
+
+        let mut response = String::new();
+        stream.take(AUTH_HTTP_MAX_RESPONSE).read_to_string(&mut response)?;
+        let Some((head, body)) = response.split_once("\r\n\r\n") else {
+            anyhow::bail!("malformed auth response");
+        };
+        let status = head.split_whitespace().nth(1).unwrap_or_default();
+        match status {
+            "200" => Ok(AccountId(body.trim().parse()?)),
+            "401" | "403" | "404" => anyhow::bail!("unknown session"),
+            _ => anyhow::bail!("auth service replied: {status}")
+        }
+    }
+}
+
+// local stand in for the backend session service, serves any Authenticator
+pub fn spawn_mock_auth_service(listener: TcpListener, authenticator: Arc<dyn Authenticator>) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = serve_session(stream, authenticator.as_ref()) {
+                        warn!("failed to serve session: {e}");
+                    }
+                }
+                Err(e) => warn!("failed to accept session request: {e}")
+            }
+        }
+    });
+}
+
+fn serve_session(mut stream: TcpStream, authenticator: &dyn Authenticator) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(AUTH_HTTP_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // headers are not used
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let session = request_line.strip_prefix("GET ")
+    .and_then(|r| r.split_whitespace().next())
+    .and_then(|path| path.strip_prefix("/sessions/"))
+    .and_then(|s| Uuid::parse_str(s).ok());
+    let (status, body) = match session.map(|s| authenticator.authenticate(s)) {
+        Some(Ok(AccountId(account))) => ("200 OK", account.to_string()),
+        Some(Err(_)) | None => ("404 Not Found", String::new())
+    };
+
+    write!(
+        stream,
+        "HTTP/1.0 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+// insert to require authentication
+#[derive(Resource, Clone)]
+pub struct Authentication(Arc<dyn Authenticator>);
+
+impl Authentication {
+    #[inline]
+    pub fn new(authenticator: impl Authenticator) -> Self {
+        Self(Arc::new(authenticator))
+    }
+}
+
+// server side, account_id is None when authentication is disabled
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ClientAuthenticated {
+    pub client_id: ClientId,
+    pub account_id: Option<AccountId>
+}
+
+#[derive(Resource, Default)]
+struct PendingAuthentications(HashMap<ClientId, Task<anyhow::Result<AccountId>>>);
+
+// connection handlers run after this set and read ClientAuthenticated
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct AuthSet;
+
+pub struct AuthPlugin;
+
+impl Plugin for AuthPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingAuthentications>()
+        .add_event::<ClientAuthenticated>()
+        .add_systems(PreUpdate, (
+            start_authentication,
+            poll_authentication
+        ).chain(
+        ).in_set(AuthSet
+        ).after(ServerSet::Receive));
+    }
+}
+
+fn start_authentication(
+    mut events: EventReader<ServerEvent>,
+    mut pending: ResMut<PendingAuthentications>,
+    mut authenticated: EventWriter<ClientAuthenticated>,
+    authentication: Option<Res<Authentication>>,
+    user_data: Res<ConnectionUserData>,
+    mut renet: Option<ResMut<RenetServer>>
+) {
+    for e in events.read() {
+        match e {
+            ServerEvent::ClientConnected { client_id } => {
+                let Some(authentication) = authentication.as_ref() else {
+                    authenticated.send(ClientAuthenticated {
+                        client_id: *client_id,
+                        account_id: None
+                    });
+                    continue;
+                };
+                let Some(session_id) = user_data.get(*client_id)
+                .map(|u| u.session_id()) else {
+                    reject(*client_id, "no session", renet.as_deref_mut());
+                    continue;
+                };
+
+                let authenticator = authentication.0.clone();
+                let task = IoTaskPool::get()
+                .spawn(async move { authenticator.authenticate(session_id) });
+                pending.0.insert(*client_id, task);
+            }
+            ServerEvent::ClientDisconnected { client_id, .. } => {
+                // result is dropped with the task
+                pending.0.remove(client_id);
+            }
+        }
+    }
+}
+
+fn poll_authentication(
+    mut pending: ResMut<PendingAuthentications>,
+    mut authenticated: EventWriter<ClientAuthenticated>,
+    mut renet: Option<ResMut<RenetServer>>
+) {
+    let mut finished = Vec::new();
+    for (client_id, task) in pending.0.iter_mut() {
+        if let Some(result) = block_on(future::poll_once(task)) {
+            finished.push((*client_id, result));
+        }
+    }
+
+    for (client_id, result) in finished {
+        pending.0.remove(&client_id);
+        match result {
+            Ok(account_id) => {
+                info!("client: {client_id:?} authenticated as: {account_id:?}");
+                authenticated.send(ClientAuthenticated {
+                    client_id,
+                    account_id: Some(account_id)
+                });
+            }
+            Err(e) => reject(client_id, &e.to_string(), renet.as_deref_mut())
+        }
+    }
+}
+
+fn reject(client_id: ClientId, reason: &str, renet: Option<&mut RenetServer>) {
+    warn!("client: {client_id:?} failed authentication: {reason}");
+    if let Some(renet) = renet {
+        renet.disconnect(renet::ClientId::from_raw(client_id.get()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+    fn session() -> Uuid {
+        Uuid::parse_str(SESSION).unwrap()
+    }
+
+    fn mock_service(authenticator: impl Authenticator) -> HttpAuthenticator {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        spawn_mock_auth_service(listener, Arc::new(authenticator));
+        HttpAuthenticator::new(addr)
+    }
+
+    // replies once with a fixed response
+    fn raw_service(response: &'static str) -> HttpAuthenticator {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        HttpAuthenticator::new(addr)
+    }
+
+    #[test]
+    fn parses_sessions() {
+        let text = format!("# sessions\n\n{SESSION} 42 # admin\n  {} 7\n", Uuid::nil());
+        let sessions = parse_sessions(&text).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions.get(&session()), Some(&AccountId(42)));
+        assert_eq!(sessions.get(&Uuid::nil()), Some(&AccountId(7)));
+    }
+
+    #[test]
+    fn rejects_malformed_sessions() {
+        for text in [
+            SESSION.to_string(),
+            format!("{SESSION} 42 extra"),
+            "not-a-uuid 42".to_string(),
+            format!("{SESSION} -1"),
+            format!("{SESSION} 42\n{SESSION}")
+        ] {
+            assert!(parse_sessions(&text).is_err(), "accepted: {text:?}");
+        }
+        let e = parse_sessions(&format!("{SESSION} 42\n\nbroken")).unwrap_err();
+        assert!(e.to_string().starts_with("line 3:"), "{e}");
+    }
+
+    #[test]
+    fn http_accepts_known_session() {
+        let auth = mock_service(InMemoryAuthenticator::new()
+            .with_session(session(), AccountId(42))
+        );
+        assert_eq!(auth.authenticate(session()).unwrap(), AccountId(42));
+        assert!(auth.authenticate(Uuid::nil()).is_err());
+    }
+
+    #[test]
+    fn http_rejects_bad_responses() {
+        let auth = raw_service("HTTP/1.0 500 Internal Server Error\r\n\r\n");
+        let e = auth.authenticate(session()).unwrap_err();
+        assert_eq!(e.to_string(), "auth service replied: 500");
+
+        let auth = raw_service("HTTP/1.0 200 OK\r\n\r\nnot an account");
+        assert!(auth.authenticate(session()).is_err());
+
+        let auth = raw_service("HTTP/1.0 200 OK\r\n");
+        let e = auth.authenticate(session()).unwrap_err();
+        assert_eq!(e.to_string(), "malformed auth response");
+    }
+
+    #[test]
+    fn http_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let auth = HttpAuthenticator::new(listener.local_addr().unwrap())
+        .with_timeout(Duration::from_millis(100));
+        // accepted by the backlog, never answered
+        assert!(auth.authenticate(session()).is_err());
+        drop(listener);
+    }
+}
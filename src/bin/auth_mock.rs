@@ -0,0 +1,29 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener},
+    sync::Arc,
+    thread
+};
+use bevy_netcharacon_dev::{
+    auth::*,
+    config::*
+};
+
+const DEFAULT_AUTH_MOCK_PORT: u16 = 5100;
+
+// stand in for the backend session service
+// auth_mock --sessions <path> [--port <port>]
+// serves the session file for server --auth-service 127.0.0.1:<port>
+fn main() {
+    let path = get_arg_value("--sessions").expect("--sessions <path> is required");
+    let port = get_arg_value("--port")
+    .map_or(DEFAULT_AUTH_MOCK_PORT, |p| p.parse().expect("port should be a number"));
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+
+    let listener = TcpListener::bind(addr).expect("failed to bind auth mock");
+    println!("auth mock serving: {path} on: {addr}");
+    spawn_mock_auth_service(listener, Arc::new(FileAuthenticator::new(path)));
+    // service runs on its own thread until killed
+    loop {
+        thread::park();
+    }
+}
@@ -1,15 +1,35 @@
 use std::net::{IpAddr, Ipv4Addr};
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::Uuid};
 use bevy_replicon::prelude::*;
 use bevy_netcharacon_dev::{
     *,
     client_builder::*, 
+    client_demo::*,
     config::*, 
-    game_client::*
+    game_client::*,
+    input_mapping::*
 };
 
 fn main() {
     let mut app = App::new();
+
+    // client --play-demo <path>
+    // no connection to server
+    if let Some(path) = get_arg_value("--play-demo") {
+        match DemoPlayback::open(&path) {
+            Ok(playback) => {
+                app.add_plugins(DefaultPlugins)
+                .add_plugins(DemoPlaybackPlugin)
+                .insert_resource(playback)
+                .run();
+                return;
+            }
+            Err(e) => {
+                panic!("{e}");
+            }
+        }
+    }
+
     let builder = ClientBuilder{
         client_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
         server_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
@@ -18,7 +38,13 @@ fn main() {
         client_id: get_dev_client_id(),
         protocol_id: get_dev_protocol_id(),
         private_key: get_dev_private_key(),
-        user_data: get_dev_user_data(),
+        // client --room <id> --session <uuid>
+        user_data: get_dev_user_data(
+            get_arg_value("--room")
+            .map_or(0, |r| r.parse().expect("room id should be a number")),
+            get_arg_value("--session")
+            .map(|s| Uuid::parse_str(&s).expect("session should be a uuid"))
+        ),
         token_expire_seconds: DEV_TOKEN_EXPIRE_SEC,
     };
 
@@ -26,9 +52,44 @@ fn main() {
     .add_plugins(builder.build_replicon())
     .add_plugins((
         GameCommonPlugin, 
-        GameClientPlugin
+        GameClientPlugin,
+        DemoRecorderPlugin
     ));
 
+    // client --input-map <path>
+    // written with defaults when missing, rebinds are saved back
+    if let Some(path) = get_arg_value("--input-map") {
+        let map = if std::path::Path::new(&path).exists() {
+            match InputMap::load(&path) {
+                Ok(map) => map,
+                Err(e) => {
+                    panic!("{e}");
+                }
+            }
+        } else {
+            let map = InputMap::default();
+            if let Err(e) = map.save(&path) {
+                panic!("{e}");
+            }
+            map
+        };
+        app.insert_resource(map)
+        .insert_resource(InputMapPath(path.into()));
+    }
+
+    // client --record-demo <path>
+    if let Some(path) = get_arg_value("--record-demo") {
+        match DemoRecorder::create(&path) {
+            Ok(recorder) => {
+                info!("recording demo to: {path}");
+                app.insert_resource(recorder);
+            }
+            Err(e) => {
+                panic!("{e}");
+            }
+        }
+    }
+
     match builder.build_transport(app.world.resource::<RepliconChannels>()) {
         Ok((client, renet, netcode)) => {
             app.insert_resource(client)
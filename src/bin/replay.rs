@@ -0,0 +1,59 @@
+use bevy::{
+    prelude::*,
+    log::{LogPlugin, Level}
+};
+use bevy_replicon::prelude::*;
+use bevy_netcharacon_dev::{
+    *,
+    game_server::*,
+    server_recorder::*
+};
+
+// replay <path>
+// prints trajectories as bits so outputs can be diffed
+fn main() {
+    let Some(path) = std::env::args().nth(1) else {
+        panic!("usage: replay <record file>");
+    };
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        LogPlugin{
+            level: Level::WARN,
+            ..default()
+        }
+    ))
+    .add_plugins(RepliconPlugins.build().disable::<ClientPlugin>())
+    .add_plugins((
+        GameCommonPlugin,
+        GameServerPlugin,
+        ServerReplayPlugin
+    ));
+
+    match ServerReplay::open(&path) {
+        Ok(replay) => {
+            app.insert_resource(replay);
+        }
+        Err(e) => {
+            panic!("{e}");
+        }
+    }
+
+    app.finish();
+    app.cleanup();
+    while !app.world.resource::<ServerReplay>()
+    .is_finished(app.world.resource::<SimulationTick>()) {
+        app.update();
+    }
+
+    for (client_id, samples) in app.world.resource::<Trajectories>().iter() {
+        for sample in samples.iter() {
+            let [tick, x, y, z, yaw] = sample.to_bits();
+            println!(
+                "{} {tick} {x:08x} {y:08x} {z:08x} {yaw:08x}", 
+                client_id.get()
+            );
+        }
+    }
+}
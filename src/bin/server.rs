@@ -1,5 +1,5 @@
 use std::{
-    net::{IpAddr, Ipv4Addr},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     time::Duration
 };
 use bevy::{
@@ -12,7 +12,12 @@ use bevy_netcharacon_dev::{
     *,
     config::*,
     server_builder::*,
-    game_server::*
+    server_recorder::*,
+    admin_console::*,
+    server_metrics::*,
+    game_server::*,
+    auth::*,
+    access_list::*
 };
 
 fn main() {
@@ -38,9 +43,60 @@ fn main() {
     .add_plugins(builder.build_replicon())
     .add_plugins((
         GameCommonPlugin,
-        GameServerPlugin
+        GameServerPlugin,
+        ServerRecorderPlugin,
+        AdminConsolePlugin{
+            stdin: true,
+            // admin socket is loopback only
+            listen_addr: get_arg_value("--admin-port")
+                .and_then(|p| p.parse::<u16>().ok())
+                .map(|p| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), p))
+        },
+        ServerMetricsPlugin{
+            listen_addr: get_arg_value("--metrics-port")
+                .and_then(|p| p.parse::<u16>().ok())
+                .map(|p| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), p)),
+            ..default()
+        }
     ));
 
+    // server --auth-file <path> or --auth-service <addr>
+    // every client is accepted without either
+    if let Some(path) = get_arg_value("--auth-file") {
+        info!("authenticating sessions from: {path}");
+        app.insert_resource(Authentication::new(FileAuthenticator::new(path)));
+    } else if let Some(addr) = get_arg_value("--auth-service") {
+        let addr = addr.parse::<SocketAddr>().expect("auth service should be an address");
+        info!("authenticating sessions with: {addr}");
+        app.insert_resource(Authentication::new(HttpAuthenticator::new(addr)));
+    }
+
+    // server --access-list <path>
+    // bans and allowlist survive restarts, in memory without it
+    if let Some(path) = get_arg_value("--access-list") {
+        match AccessList::load(&path) {
+            Ok(access) => {
+                info!("access list loaded from: {path}");
+                app.insert_resource(access);
+            }
+            Err(e) => {
+                panic!("{e}");
+            }
+        }
+    }
+
+    if let Some(path) = get_arg_value("--record") {
+        match ServerRecorder::create(&path) {
+            Ok(recorder) => {
+                info!("recording server session to: {path}");
+                app.insert_resource(recorder);
+            }
+            Err(e) => {
+                panic!("{e}");
+            }
+        }
+    }
+
     match builder.build_transport(app.world.resource::<RepliconChannels>()) {
         Ok((server, renet, netcode)) => {
             app.insert_resource(server)
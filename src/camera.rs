@@ -0,0 +1,184 @@
+use std::f32::consts::FRAC_PI_2;
+use bevy::{
+    prelude::*,
+    input::mouse::MouseMotion
+};
+use bevy_xpbd_3d::prelude::*;
+use crate::{
+    *,
+    config::PHYSICS_FIXED_TICK_RATE,
+    level::CAMERA_POSITION,
+    game_client::LocalCharacter,
+    input_mapping::ActionInput
+};
+
+pub const CAMERA_PIVOT_HEIGHT: f32 = CHARACTER_HIGHT;
+pub const CAMERA_EYE_HEIGHT: f32 = CHARACTER_HIGHT * 0.5 + CHARACTER_OFFSET;
+pub const CAMERA_BOOM_LENGTH: f32 = 8.0;
+pub const CAMERA_BOOM_MIN_LENGTH: f32 = 0.5;
+// keeps near plane off the wall
+pub const CAMERA_BOOM_MARGIN: f32 = 0.2;
+// per second, only when extending
+pub const CAMERA_BOOM_EXTEND_SPEED: f32 = 10.0;
+pub const CAMERA_PITCH_SENSITIVITY: f32 = 0.003;
+pub const CAMERA_MIN_PITCH: f32 = -FRAC_PI_2 * 0.95;
+pub const CAMERA_MAX_PITCH: f32 = FRAC_PI_2 * 0.95;
+pub const CAMERA_DEFAULT_PITCH: f32 = -0.3;
+
+const SWITCH_MODE: KeyCode = KeyCode::KeyV;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CameraMode {
+    #[default]
+    ThirdPerson,
+    FirstPerson
+}
+
+#[derive(Component)]
+pub struct FollowCamera {
+    pub mode: CameraMode,
+    pitch: f32,
+    boom_length: f32
+}
+
+impl Default for FollowCamera {
+    fn default() -> Self {
+        Self {
+            mode: CameraMode::default(),
+            pitch: CAMERA_DEFAULT_PITCH,
+            boom_length: CAMERA_BOOM_LENGTH
+        }
+    }
+}
+
+pub struct FollowCameraPlugin;
+
+impl Plugin for FollowCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_follow_camera)
+        .add_systems(Update, (
+            switch_camera_mode_system,
+            camera_pitch_system,
+            follow_camera_system
+        ).chain());
+    }
+}
+
+fn setup_follow_camera(mut commands: Commands) {
+    // stays like fixed camera until local character is spawned
+    commands.spawn((
+        Camera3dBundle{
+            transform: Transform::from_translation(CAMERA_POSITION)
+                .looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        },
+        FollowCamera::default()
+    ));
+}
+
+fn switch_camera_mode_system(
+    mut cameras: Query<&mut FollowCamera>,
+    mut characters: Query<&mut Visibility, With<LocalCharacter>>,
+    keyboard: Res<ButtonInput<KeyCode>>
+) {
+    let Ok(mut camera) = cameras.get_single_mut() else {
+        return;
+    };
+
+    if keyboard.just_pressed(SWITCH_MODE) {
+        camera.mode = match camera.mode {
+            CameraMode::ThirdPerson => CameraMode::FirstPerson,
+            CameraMode::FirstPerson => CameraMode::ThirdPerson
+        };
+        info!("camera mode: {:?}", camera.mode);
+    }
+
+    // own capsule would cover the whole view
+    if let Ok(mut visibility) = characters.get_single_mut() {
+        let next = match camera.mode {
+            CameraMode::ThirdPerson => Visibility::Inherited,
+            CameraMode::FirstPerson => Visibility::Hidden
+        };
+        if *visibility != next {
+            *visibility = next;
+        }
+    }
+}
+
+fn camera_pitch_system(
+    mut query: Query<&mut FollowCamera>,
+    mut mouse: EventReader<MouseMotion>,
+    input: ActionInput,
+    time: Res<Time>
+) {
+    let Ok(mut camera) = query.get_single_mut() else {
+        return;
+    };
+
+    // look stick is in mouse motion per fixed tick
+    let mut delta = input.look_stick().y * time.delta_seconds() * PHYSICS_FIXED_TICK_RATE;
+    for e in mouse.read() {
+        delta += e.delta.y * input.map().mouse_sensitivity;
+    }
+    camera.pitch = (camera.pitch - delta * CAMERA_PITCH_SENSITIVITY)
+    .clamp(CAMERA_MIN_PITCH, CAMERA_MAX_PITCH);
+}
+
+fn follow_camera_system(
+    mut cameras: Query<(&mut FollowCamera, &mut Transform), Without<LocalCharacter>>,
+    characters: Query<(Entity, &Transform), With<LocalCharacter>>,
+    rigid_bodies: Query<&RigidBody>,
+    sensors: Query<(), With<Sensor>>,
+    spatial_query: SpatialQuery,
+    time: Res<Time>
+) {
+    let Ok((mut camera, mut camera_transform)) = cameras.get_single_mut() else {
+        return;
+    };
+    let Ok((character, character_transform)) = characters.get_single() else {
+        return;
+    };
+
+    let yaw = quat_to_yaw(character_transform.rotation);
+    let rotation = Quat::from_euler(EulerRot::YXZ, yaw, camera.pitch, 0.0);
+
+    match camera.mode {
+        CameraMode::FirstPerson => {
+            camera_transform.translation = character_transform.translation
+                + Vec3::Y * CAMERA_EYE_HEIGHT;
+            camera_transform.rotation = rotation;
+        }
+        CameraMode::ThirdPerson => {
+            let pivot = character_transform.translation
+                + Vec3::Y * CAMERA_PIVOT_HEIGHT;
+            let back = rotation * Vec3::Z;
+
+            // only solid level geometry blocks the boom,
+            // not other characters or liquid and ladder volumes
+            let blocked = Direction3d::new(back).ok()
+            .and_then(|dir| spatial_query.cast_ray_predicate(
+                pivot,
+                dir,
+                CAMERA_BOOM_LENGTH,
+                true,
+                SpatialQueryFilter::from_excluded_entities([character]),
+                &|e| rigid_bodies.get(e).is_ok_and(RigidBody::is_static)
+                && !sensors.contains(e)
+            ))
+            .map(|hit| hit.time_of_impact - CAMERA_BOOM_MARGIN);
+
+            let target = blocked.unwrap_or(CAMERA_BOOM_LENGTH)
+            .clamp(CAMERA_BOOM_MIN_LENGTH, CAMERA_BOOM_LENGTH);
+            // pull in at once so the camera never clips, ease back out
+            camera.boom_length = if target < camera.boom_length {
+                target
+            } else {
+                (camera.boom_length + CAMERA_BOOM_EXTEND_SPEED * time.delta_seconds())
+                .min(target)
+            };
+
+            camera_transform.translation = pivot + back * camera.boom_length;
+            camera_transform.rotation = rotation;
+        }
+    }
+}
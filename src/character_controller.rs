@@ -2,15 +2,18 @@
 // https://github.com/Jondolf/bevy_xpbd/tree/main/crates/bevy_xpbd_3d/examples/kinematic_character_3d
 // server have to operate multiple character controller
 
-use std::f32::{consts::PI, EPSILON};
+use std::f32::consts::PI;
 use bevy::prelude::*;
 use bevy_xpbd_3d::{
     prelude::*, 
+    PhysicsSchedule,
+    PhysicsStepSet,
     SubstepSchedule, 
     SubstepSet
 };
 use crate::{
     config::PHYSICS_SUBSTEP, 
+    SimulationTick,
     instant_event_buffer::InstantEventBuffer
 };
 
@@ -19,12 +22,23 @@ pub struct CharacterControllerPlugin;
 impl Plugin for CharacterControllerPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<ControllerAction>()
+        .add_event::<CharacterLanded>()
         .add_systems(SubstepSchedule, (
+            update_submersion_system,
+            update_climbing_system,
             control_system,
             apply_gravity_system,
+            swim_system,
+            steep_slope_system,
             apply_movement_damping_system
         ).chain(
         ).before(SubstepSet::Integrate))
+        .add_systems(SubstepSchedule, (
+            collide_and_slide_system,
+            swept_motion_system
+        ).chain(
+        ).after(SubstepSet::Integrate
+        ).before(SubstepSet::NarrowPhase))
         .add_systems(SubstepSchedule, 
             kinematic_collisions_system
             .in_set(SubstepSet::SolveUserConstraints)
@@ -32,14 +46,43 @@ impl Plugin for CharacterControllerPlugin {
         .add_systems(SubstepSchedule,
             update_grounded_system
             .after(SubstepSet::ApplyTranslation)
-        );
+        )
+        // once per step, ground caster hits are updated per step
+        .add_systems(PhysicsSchedule, (
+            ground_snap_system,
+            fall_tracking_system
+        ).chain(
+        ).after(PhysicsStepSet::SpatialQuery));
     }
 }
 
 #[derive(Event)]
 pub enum ControllerAction {
+    // length <= 1, speed scales with length
     Move(Vec2),
-    Jump
+    Jump,
+    // -1 sinks, 1 rises, only while swimming
+    Swim(f32)
+}
+
+impl ControllerAction {
+    // input comes from network, never trust length
+    // None for zero or non finite input
+    #[inline]
+    pub fn from_move_input(linear: Vec2) -> Option<Self> {
+        if !linear.is_finite() || linear == Vec2::ZERO {
+            return None;
+        }
+        Some(ControllerAction::Move(linear.clamp_length_max(1.0)))
+    }
+
+    #[inline]
+    pub fn from_swim_input(vertical: f32) -> Option<Self> {
+        if !vertical.is_finite() || vertical == 0.0 {
+            return None;
+        }
+        Some(ControllerAction::Swim(vertical.clamp(-1.0, 1.0)))
+    }
 }
 
 #[derive(Component)]
@@ -49,6 +92,66 @@ pub struct CharacterController;
 #[component(storage = "SparseSet")]
 pub struct Grounded;
 
+// standing on ground steeper than MaxSlopeAngle, with its normal
+// not grounded, so no jump and no walking damping
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct OnSteepSlope(pub Vec3);
+
+// at least SWIM_SUBMERSION under a liquid surface
+// not grounded, walking damping is replaced by liquid drag
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct Swimming;
+
+// sensor volume, characters treat it as its bounding box
+#[derive(Component, Clone, Copy)]
+pub struct LiquidVolume {
+    // relative to characters, above 1 floats
+    pub density: f32,
+    // velocity lost per second when fully submerged
+    pub drag: f32
+}
+
+// deepest liquid the character is in, updated every substep
+#[derive(Component, Default, Clone, Copy)]
+pub struct Submersion {
+    // 0 dry, 1 fully under the surface
+    pub fraction: f32,
+    // world y of the liquid surface
+    pub surface: f32,
+    pub density: f32,
+    pub drag: f32
+}
+
+impl Submersion {
+    // head above the surface
+    #[inline]
+    pub fn is_at_surface(&self) -> bool {
+        self.fraction > 0.0 && self.fraction < 1.0
+    }
+}
+
+// attached to a climbable volume, gravity is suspended
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct Climbing;
+
+// sensor volume, characters whose center is in its bounding box climb
+#[derive(Component, Clone, Copy)]
+pub struct Climbable {
+    // away from the climbed surface, in the volume's local space
+    pub normal: Vec3
+}
+
+#[derive(Component, Default, Clone, Copy)]
+pub struct Climber {
+    // world normal of the climbed surface
+    pub normal: Vec3,
+    // jumped off, attaches again after leaving the volume or landing
+    detached: bool
+}
+
 #[derive(Component)]
 pub struct Acceleration(f32);
 
@@ -64,14 +167,161 @@ pub struct Gravity(Vec3);
 #[derive(Component)]
 pub struct MaxSlopeAngle(f32);
 
+// coulomb friction coefficient when sliding down a steep slope
+#[derive(Component)]
+pub struct SteepSlopeFriction(pub f32);
+
+// grounded characters moving downhill or off a ledge are pulled down
+// to walkable ground up to distance, 0 disables
+// suspended by a jump until the character lands again
+#[derive(Component)]
+pub struct GroundSnap {
+    pub distance: f32,
+    jumped: bool,
+    // previous step ended on walkable ground, by its own hits or by snapping
+    grounded: bool
+}
+
+impl GroundSnap {
+    #[inline]
+    pub fn new(distance: f32) -> Self {
+        Self {
+            distance,
+            jumped: false,
+            grounded: false
+        }
+    }
+}
+
+// fastest downward speed since the character left the ground
+// swimming and climbing break the fall
+#[derive(Component, Default)]
+pub struct FallTracker {
+    fall_speed: f32
+}
+
+impl FallTracker {
+    // call on teleport, the fall before it does not count
+    #[inline]
+    pub fn reset(&mut self) {
+        self.fall_speed = 0.0;
+    }
+}
+
+// sent once per landing, speed is the fastest downward speed of the fall
+#[derive(Event, Clone, Copy, Debug)]
+pub struct CharacterLanded {
+    pub entity: Entity,
+    pub speed: f32
+}
+
+// dynamic bodies up to this mass follow the character at full speed,
+// heavier ones slower
+#[derive(Component)]
+pub struct PushStrength(pub f32);
+
+// how integrated motion is kept out of level geometry
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum MovementSolver {
+    // move, then push out along contact normals
+    #[default]
+    Depenetration,
+    // shape cast along the motion and slide along hit planes,
+    // contacts are still resolved after it for characters and dynamic bodies
+    CollideAndSlide
+}
+
+// fast motion of Depenetration characters is swept,
+// so they do not pass through thin colliders between substeps
+#[derive(Component, Clone, Copy)]
+pub struct ContinuousCollision {
+    // substep displacement above collider radius * this is swept
+    pub radius_fraction: f32,
+    pub enabled: bool
+}
+
+impl Default for ContinuousCollision {
+    fn default() -> Self {
+        Self {
+            radius_fraction: 0.5,
+            enabled: true
+        }
+    }
+}
+
+// character swims from this fraction under the surface
+pub const SWIM_SUBMERSION: f32 = 0.6;
+// of Acceleration, for swimming up and down
+const SWIM_VERTICAL_FACTOR: f32 = 0.5;
+
+// backward impulse of a jump off a ladder, the upward one is half JumpImpulse
+const CLIMB_DETACH_IMPULSE: f32 = 4.0;
+
+// slide planes per substep, a corner needs 2, a crease between 3 stops
+pub const SLIDE_MAX_ITERATIONS: usize = 4;
+// distance kept from hit surfaces
+pub const SLIDE_SKIN: f32 = 0.01;
+const SLIDE_MAX_HITS: u32 = 8;
+
+// response to other characters
+#[derive(Component, Clone, Copy)]
+pub struct CharacterCollision {
+    // higher priority is never pushed by lower priority
+    pub priority: i32,
+    // with same priority, penetration is split by inverse mass
+    pub mass: f32,
+    // fraction of penetration resolved per substep
+    // 1 is hard, lower values push softly over time
+    pub stiffness: f32,
+    // false to pass through other characters
+    pub enabled: bool
+}
+
+impl Default for CharacterCollision {
+    fn default() -> Self {
+        Self {
+            priority: 0,
+            mass: 1.0,
+            stiffness: 1.0,
+            enabled: true
+        }
+    }
+}
+
+impl CharacterCollision {
+    // shares of penetration resolved by self and other
+    #[inline]
+    pub fn split(&self, other: &Self) -> (f32, f32) {
+        match self.priority.cmp(&other.priority) {
+            std::cmp::Ordering::Greater => (0.0, 1.0),
+            std::cmp::Ordering::Less => (1.0, 0.0),
+            std::cmp::Ordering::Equal => {
+                let w1 = 1.0 / self.mass.max(f32::EPSILON);
+                let w2 = 1.0 / other.mass.max(f32::EPSILON);
+                (w1 / (w1 + w2), w2 / (w1 + w2))
+            }
+        }
+    }
+}
+
 #[derive(Bundle)]
 pub struct CharacterControllerBundle {
     character_controller: CharacterController,
     controller_action_buffer: InstantEventBuffer<ControllerAction>,
     rigid_body: RigidBody,
     collider: Collider,
+    collision_layers: CollisionLayers,
     ground_caster: ShapeCaster,
     gravity: Gravity,
+    character_collision: CharacterCollision,
+    push_strength: PushStrength,
+    submersion: Submersion,
+    climber: Climber,
+    fall_tracker: FallTracker,
+    steep_slope_friction: SteepSlopeFriction,
+    ground_snap: GroundSnap,
+    movement_solver: MovementSolver,
+    continuous_collision: ContinuousCollision,
     movement: MovementBundle
 }
 
@@ -87,6 +337,16 @@ const DEFAULT_ACCELERATION: Acceleration = Acceleration(60.0 * PHYSICS_SUBSTEP);
 const DEFAULT_DAMPING_FACTOR: DampingFactor = DampingFactor(0.98);
 const DEFAULT_JUMP_IMPULSE: JumpImpulse = JumpImpulse(9.0);
 const DEFAULT_MAX_SLOPE_ANGLE: MaxSlopeAngle = MaxSlopeAngle(PI * 0.45);
+const DEFAULT_PUSH_STRENGTH: PushStrength = PushStrength(2.0);
+const DEFAULT_STEEP_SLOPE_FRICTION: SteepSlopeFriction = SteepSlopeFriction(0.2);
+pub const DEFAULT_GROUND_SNAP_DISTANCE: f32 = 0.4;
+// ground steeper than this is a wall, not a slope
+const STEEP_SLOPE_MAX_ANGLE: f32 = PI * 0.49;
+// ground caster hits farther than this do not make the character grounded
+const GROUNDED_DISTANCE: f32 = 0.2;
+const GROUND_CASTER_MAX_HITS: u32 = 4;
+// left between snapped character and ground
+const GROUND_SNAP_SKIN: f32 = 0.01;
 
 impl Default for MovementBundle {
     fn default() -> Self {
@@ -104,7 +364,6 @@ impl CharacterControllerBundle {
     pub fn new(collider: Collider, gravity: Vec3) -> Self {
         const SCALE: f32 = 0.99;
         const SUBDIVISIONS: u32 = 10;
-        const MAX_CAST_TIME: f32 = 0.2;
 
         let mut caster_shape = collider.clone();
         caster_shape.set_scale(Vec3::ONE * SCALE, SUBDIVISIONS);
@@ -114,17 +373,83 @@ impl CharacterControllerBundle {
             controller_action_buffer: InstantEventBuffer::<ControllerAction>::new(),
             rigid_body: RigidBody::Kinematic,
             collider,
+            collision_layers: CollisionLayers::default(),
             ground_caster: ShapeCaster::new(
                 caster_shape, 
                 Vec3::ZERO, 
                 Quat::IDENTITY, 
                 Direction3d::NEG_Y
-            ).with_max_time_of_impact(MAX_CAST_TIME),
+            )
+            .with_max_time_of_impact(GROUNDED_DISTANCE.max(DEFAULT_GROUND_SNAP_DISTANCE))
+            // sensors are skipped, ground may be behind one
+            .with_max_hits(GROUND_CASTER_MAX_HITS),
             gravity: Gravity(gravity),
+            character_collision: CharacterCollision::default(),
+            push_strength: DEFAULT_PUSH_STRENGTH,
+            submersion: Submersion::default(),
+            climber: Climber::default(),
+            fall_tracker: FallTracker::default(),
+            steep_slope_friction: DEFAULT_STEEP_SLOPE_FRICTION,
+            ground_snap: GroundSnap::new(DEFAULT_GROUND_SNAP_DISTANCE),
+            movement_solver: MovementSolver::default(),
+            continuous_collision: ContinuousCollision::default(),
             movement: MovementBundle::default()
         }
     }
 
+    #[inline]
+    pub fn with_steep_slope_friction(mut self, friction: f32) -> Self {
+        self.steep_slope_friction = SteepSlopeFriction(friction.max(0.0));
+        self
+    }
+
+    #[inline]
+    pub fn with_max_slope_angle(mut self, max_slope_angle: f32) -> Self {
+        self.movement.max_slope_angle = MaxSlopeAngle(max_slope_angle);
+        self
+    }
+
+    // ground caster reaches at least distance
+    #[inline]
+    pub fn with_ground_snap(mut self, distance: f32) -> Self {
+        let distance = distance.max(0.0);
+        self.ground_snap = GroundSnap::new(distance);
+        self.ground_caster.max_time_of_impact = GROUNDED_DISTANCE.max(distance);
+        self
+    }
+
+    #[inline]
+    pub fn with_continuous_collision(mut self, continuous_collision: ContinuousCollision) -> Self {
+        self.continuous_collision = continuous_collision;
+        self
+    }
+
+    #[inline]
+    pub fn with_movement_solver(mut self, movement_solver: MovementSolver) -> Self {
+        self.movement_solver = movement_solver;
+        self
+    }
+
+    #[inline]
+    pub fn with_push_strength(mut self, push_strength: f32) -> Self {
+        self.push_strength = PushStrength(push_strength);
+        self
+    }
+
+    #[inline]
+    pub fn with_character_collision(mut self, character_collision: CharacterCollision) -> Self {
+        self.character_collision = character_collision;
+        self
+    }
+
+    // ground detection only sees what the character collides with
+    #[inline]
+    pub fn with_collision_layers(mut self, layers: CollisionLayers) -> Self {
+        self.collision_layers = layers;
+        self.ground_caster.query_filter = SpatialQueryFilter::from_mask(layers.filters);
+        self
+    }
+
     #[inline]
     pub fn with_movement(
         mut self,
@@ -152,38 +477,176 @@ fn update_grounded_system(
         Option<&MaxSlopeAngle>
     ),
         With<CharacterController>
-    >
+    >,
+    sensors: Query<(), With<Sensor>>
 ) {
     for (e, hits, rot, slope_angle) in query.iter_mut() {
-        let is_grounded = hits.iter().any(|hit| {
-            if let Some(angle) = slope_angle {
-                rot.rotate(-hit.normal2)
-                .angle_between(Vec3::Y)
-                .abs() <= angle.0
-            } else {
-                true
+        // liquid volumes and other triggers are not ground
+        let normals = hits.iter()
+        .filter(|hit| hit.time_of_impact <= GROUNDED_DISTANCE)
+        .filter(|hit| !sensors.contains(hit.entity))
+        .map(|hit| rot.rotate(-hit.normal2));
+        let max_angle = slope_angle.map_or(PI, |a| a.0);
+        let mut is_grounded = false;
+        let mut steep = None::<Vec3>;
+        for normal in normals {
+            let angle = normal.angle_between(Vec3::Y).abs();
+            if angle <= max_angle {
+                is_grounded = true;
+                break;
             }
-        });
+            // least steep of the steep surfaces
+            if angle < STEEP_SLOPE_MAX_ANGLE
+            && steep.is_none_or(|s| normal.y > s.y) {
+                steep = Some(normal);
+            }
+        }
 
         if is_grounded {
             commands.entity(e)
-            .insert(Grounded);
-        } else {
+            .insert(Grounded)
+            .remove::<OnSteepSlope>();
+        } else if let Some(normal) = steep {
             commands.entity(e)
+            .insert(OnSteepSlope(normal.normalize()))
             .remove::<Grounded>();
+        } else {
+            commands.entity(e)
+            .remove::<(Grounded, OnSteepSlope)>();
+        }
+    }
+}
+
+// hits are from the end of this step, snapping is not seen by them
+// grounded state is kept from the hits of the previous step,
+// so ledges deeper than GROUNDED_DISTANCE snap up to distance
+#[allow(clippy::type_complexity)]
+fn ground_snap_system(
+    mut query: Query<(
+        &mut GroundSnap,
+        &ShapeCaster,
+        &ShapeHits,
+        &Collider,
+        &Rotation,
+        &MaxSlopeAngle,
+        &mut Position,
+        &mut LinearVelocity,
+        Has<Swimming>,
+        Has<Climbing>
+    ),
+        With<CharacterController>
+    >,
+    sensors: Query<(), With<Sensor>>
+) {
+    for (
+        mut snap,
+        caster,
+        hits,
+        collider,
+        rot,
+        slope_angle,
+        mut pos,
+        mut vel,
+        is_swimming,
+        is_climbing
+    ) in query.iter_mut() {
+        let was_grounded = snap.grounded;
+        snap.grounded = false;
+        if is_swimming || is_climbing {
+            continue;
+        }
+
+        let toi = hits.iter()
+        .filter(|hit| !sensors.contains(hit.entity))
+        .filter(|hit| rot.rotate(-hit.normal2)
+            .angle_between(Vec3::Y)
+            .abs() <= slope_angle.0
+        )
+        .map(|hit| hit.time_of_impact)
+        .reduce(f32::min);
+        let on_ground = toi.is_some_and(|toi| toi <= GROUNDED_DISTANCE);
+
+        if snap.jumped {
+            if on_ground && vel.y <= 0.0 {
+                snap.jumped = false;
+                snap.grounded = true;
+            }
+            continue;
+        }
+        snap.grounded = on_ground;
+        if !was_grounded || vel.y > 0.0 || snap.distance <= 0.0 {
+            continue;
+        }
+
+        let Some(toi) = toi else {
+            continue;
+        };
+        // caster shape is scaled down, its bottom is above the collider bottom
+        let caster_gap = caster.shape.aabb(Vec3::ZERO, *rot).min.y
+        - collider.aabb(Vec3::ZERO, *rot).min.y;
+        let drop = toi - caster_gap.max(0.0) - GROUND_SNAP_SKIN;
+        if drop <= 0.0 || drop > snap.distance {
+            continue;
+        }
+
+        pos.y -= drop;
+        vel.y = 0.0;
+        snap.grounded = true;
+    }
+}
+
+// velocity of the landing step is already cut by the ground,
+// so the fall speed is kept from the airborne steps
+#[allow(clippy::type_complexity)]
+fn fall_tracking_system(
+    mut query: Query<(
+        Entity,
+        &mut FallTracker,
+        &LinearVelocity,
+        Has<Grounded>,
+        Has<Swimming>,
+        Has<Climbing>
+    ),
+        With<CharacterController>
+    >,
+    mut landed: EventWriter<CharacterLanded>
+) {
+    for (e, mut tracker, vel, is_grounded, is_swimming, is_climbing) in query.iter_mut() {
+        if is_swimming || is_climbing {
+            tracker.fall_speed = 0.0;
+            continue;
+        }
+        if !is_grounded {
+            tracker.fall_speed = tracker.fall_speed.max(-vel.y);
+            continue;
+        }
+        if tracker.fall_speed > 0.0 {
+            landed.send(CharacterLanded {
+                entity: e,
+                speed: tracker.fall_speed
+            });
+            tracker.fall_speed = 0.0;
         }
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn control_system(
     mut query: Query<(
         &mut InstantEventBuffer<ControllerAction>,
         &Acceleration,
         &JumpImpulse,
         &mut LinearVelocity,
-        Has<Grounded>
+        Option<&mut GroundSnap>,
+        Option<&OnSteepSlope>,
+        Option<&Submersion>,
+        Option<&mut Climber>,
+        Has<Grounded>,
+        Has<Swimming>,
+        Has<Climbing>
     )>,
-    time: Res<Time>
+    time: Res<Time>,
+    tick: Res<SimulationTick>
 ) {
     if query.is_empty() {
         return;
@@ -191,18 +654,71 @@ fn control_system(
 
     let delta_time = time.delta_seconds();
 
-    for (mut controls, accel, jump, mut vel, is_grounded) in query.iter_mut() {
-        for control in controls.read() {
+    for (
+        mut controls, 
+        accel, 
+        jump, 
+        mut vel, 
+        mut snap, 
+        steep_slope, 
+        submersion,
+        mut climber,
+        is_grounded,
+        is_swimming,
+        is_climbing
+    ) in query.iter_mut() {
+        // away from the slope, horizontal
+        let downhill = steep_slope.map(|s| Vec3::new(s.0.x, 0.0, s.0.z).normalize_or_zero());
+        for (_, control) in controls.read(tick.get()) {
             match control {
                 ControllerAction::Move(dir) => {
+                    let dir = dir.clamp_length_max(1.0);
+                    let mut dir = Vec3::new(dir.x, 0.0, -dir.y);
+                    // into the ladder climbs up, away climbs down,
+                    // sideways moves along it
+                    if let (true, Some(climber)) = (is_climbing, climber.as_ref()) {
+                        let out = climber.normal;
+                        let side = out.cross(Vec3::Y).normalize_or_zero();
+                        dir = Vec3::Y * -dir.dot(out) + side * dir.dot(side);
+                        vel.0 += dir * accel.0 * delta_time;
+                        continue;
+                    }
+                    // walking up a steep slope is not possible
+                    if let Some(downhill) = downhill {
+                        dir -= downhill * dir.dot(downhill).min(0.0);
+                    }
                     vel.x += dir.x * accel.0 * delta_time;
-                    vel.z -= dir.y * accel.0 * delta_time;
+                    vel.z += dir.z * accel.0 * delta_time;
                 }
                 ControllerAction::Jump => {
-                    if is_grounded {
+                    if let (true, Some(climber)) = (is_climbing, climber.as_mut()) {
+                        vel.0 = climber.normal * CLIMB_DETACH_IMPULSE + Vec3::Y * jump.0 * 0.5;
+                        climber.detached = true;
+                        continue;
+                    }
+                    // climbs out of liquid from the surface
+                    let can_jump = if is_swimming {
+                        submersion.is_some_and(|s| s.is_at_surface())
+                    } else {
+                        is_grounded
+                    };
+                    if can_jump {
                         vel.y = jump.0;
+                        if let Some(snap) = snap.as_mut() {
+                            snap.jumped = true;
+                        }
                     }
                 }
+                ControllerAction::Swim(vertical) => {
+                    if !is_swimming {
+                        continue;
+                    }
+                    // buoyancy holds the character at the surface
+                    if vertical > 0.0 && submersion.is_some_and(|s| s.is_at_surface()) {
+                        continue;
+                    }
+                    vel.y += vertical.clamp(-1.0, 1.0) * accel.0 * SWIM_VERTICAL_FACTOR * delta_time;
+                }
             }
         }
 
@@ -211,7 +727,7 @@ fn control_system(
 }
 
 fn apply_gravity_system(
-    mut query: Query<(&Gravity, &mut LinearVelocity)>,
+    mut query: Query<(&Gravity, &mut LinearVelocity), Without<Climbing>>,
     time: Res<Time>
 ) {
     if query.is_empty() {
@@ -225,31 +741,403 @@ fn apply_gravity_system(
     }
 }
 
+#[allow(clippy::type_complexity)]
+fn update_submersion_system(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &Position,
+        &Rotation,
+        &Collider,
+        Option<&CollisionLayers>,
+        &mut Submersion
+    ),
+        With<CharacterController>
+    >,
+    liquids: Query<(
+        &LiquidVolume,
+        &Position,
+        &Rotation,
+        &Collider,
+        Option<&CollisionLayers>
+    ),
+        Without<CharacterController>
+    >
+) {
+    for (e, pos, rot, collider, layers, mut submersion) in query.iter_mut() {
+        let layers = layers.copied().unwrap_or_default();
+        let body = collider.aabb(pos.0, *rot);
+        let height = (body.max.y - body.min.y).max(f32::EPSILON);
+
+        let mut deepest = Submersion::default();
+        for (liquid, liquid_pos, liquid_rot, liquid_collider, liquid_layers) in liquids.iter() {
+            if !layers.interacts_with(liquid_layers.copied().unwrap_or_default()) {
+                continue;
+            }
+            let volume = liquid_collider.aabb(liquid_pos.0, *liquid_rot);
+            let inside = pos.x >= volume.min.x && pos.x <= volume.max.x
+            && pos.z >= volume.min.z && pos.z <= volume.max.z
+            && body.max.y >= volume.min.y;
+            if !inside {
+                continue;
+            }
+            let fraction = ((volume.max.y - body.min.y) / height).clamp(0.0, 1.0);
+            if fraction > deepest.fraction {
+                deepest = Submersion {
+                    fraction,
+                    surface: volume.max.y,
+                    density: liquid.density,
+                    drag: liquid.drag
+                };
+            }
+        }
+        *submersion = deepest;
+
+        if deepest.fraction >= SWIM_SUBMERSION {
+            commands.entity(e)
+            .insert(Swimming);
+        } else {
+            commands.entity(e)
+            .remove::<Swimming>();
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_climbing_system(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &Position,
+        Option<&CollisionLayers>,
+        &mut Climber,
+        &LinearVelocity,
+        Has<Grounded>
+    ),
+        With<CharacterController>
+    >,
+    climbables: Query<(
+        &Climbable,
+        &Position,
+        &Rotation,
+        &Collider,
+        Option<&CollisionLayers>
+    ),
+        Without<CharacterController>
+    >
+) {
+    for (e, pos, layers, mut climber, vel, is_grounded) in query.iter_mut() {
+        let layers = layers.copied().unwrap_or_default();
+        let normal = climbables.iter()
+        .filter(|(.., l)| layers.interacts_with(l.copied().unwrap_or_default()))
+        .find(|(_, p, r, c, _)| {
+            let volume = c.aabb(p.0, **r);
+            pos.0.cmpge(volume.min).all() && pos.0.cmple(volume.max).all()
+        })
+        .map(|(climbable, _, r, ..)| r.rotate(climbable.normal).normalize_or_zero());
+
+        let Some(normal) = normal else {
+            climber.detached = false;
+            commands.entity(e)
+            .remove::<Climbing>();
+            continue;
+        };
+        // landing at the foot of the ladder allows climbing again
+        if climber.detached && is_grounded && vel.y <= 0.0 {
+            climber.detached = false;
+        }
+
+        if climber.detached {
+            commands.entity(e)
+            .remove::<Climbing>();
+        } else {
+            climber.normal = normal;
+            commands.entity(e)
+            .insert(Climbing);
+        }
+    }
+}
+
+// buoyancy and drag grow with submersion, also while wading
+fn swim_system(
+    mut query: Query<(&Submersion, &Gravity, &mut LinearVelocity), Without<Climbing>>,
+    time: Res<Time>
+) {
+    let delta_time = time.delta_seconds();
+
+    for (submersion, gravity, mut vel) in query.iter_mut() {
+        if submersion.fraction <= 0.0 {
+            continue;
+        }
+        vel.0 -= gravity.0 * submersion.density * submersion.fraction * delta_time;
+        vel.0 /= 1.0 + submersion.drag * submersion.fraction * delta_time;
+    }
+}
+
+// velocity into the slope is removed, the rest slides with friction
+fn steep_slope_system(
+    mut query: Query<(
+        &OnSteepSlope,
+        &Gravity,
+        Option<&SteepSlopeFriction>,
+        &mut LinearVelocity
+    )>,
+    time: Res<Time>
+) {
+    let delta_time = time.delta_seconds();
+
+    for (slope, gravity, friction, mut vel) in query.iter_mut() {
+        let normal = slope.0;
+        let into = vel.dot(normal);
+        if into < 0.0 {
+            vel.0 -= normal * into;
+        }
+
+        let friction = friction.map_or(0.0, |f| f.0);
+        let pressure = (-gravity.0.dot(normal)).max(0.0);
+        let speed = vel.length();
+        if speed > f32::EPSILON {
+            let slow = (friction * pressure * delta_time).min(speed);
+            vel.0 *= 1.0 - slow / speed;
+        }
+    }
+}
+
+// walking damping, steep slopes use their own friction
+#[allow(clippy::type_complexity)]
 fn apply_movement_damping_system(
-    mut query: Query<(&DampingFactor, &mut LinearVelocity)>
+    mut query: Query<
+        (&DampingFactor, &mut LinearVelocity, Has<Climbing>), 
+        (Without<OnSteepSlope>, Without<Swimming>)
+    >
 ) {
-    for (damp, mut vel) in query.iter_mut() {
+    for (damp, mut vel, is_climbing) in query.iter_mut() {
+        // climbing moves vertically like walking
+        if is_climbing {
+            vel.y *= damp.0;
+            if vel.y.abs() <= f32::EPSILON {
+                vel.y = 0.0;
+            }
+        }
         vel.x *= damp.0;
-        if vel.x.abs() <= EPSILON {
+        if vel.x.abs() <= f32::EPSILON {
             vel.x = 0.0;
         }
         vel.z *= damp.0;
-        if vel.z.abs() <= EPSILON {
+        if vel.z.abs() <= f32::EPSILON {
             vel.z = 0.0;
         }
     }
 }
 
+// replaces the translation integrated in this substep with the slid one
+// only static and kinematic level geometry blocks,
+// characters and dynamic bodies are left to contacts
+#[allow(clippy::type_complexity)]
+fn collide_and_slide_system(
+    mut query: Query<(
+        &RigidBody,
+        &MovementSolver,
+        &Position,
+        &Rotation,
+        &Collider,
+        &CollisionLayers,
+        &mut AccumulatedTranslation,
+        &mut LinearVelocity
+    ),
+        With<CharacterController>
+    >,
+    blockers: Query<&RigidBody, Without<CharacterController>>,
+    col_parents: Query<&ColliderParent, Without<Sensor>>,
+    spatial_query: Res<SpatialQueryPipeline>
+) {
+    for (
+        rb, 
+        solver, 
+        pos, 
+        rot, 
+        collider, 
+        layers, 
+        mut translation, 
+        mut vel
+    ) in query.iter_mut() {
+        if *solver != MovementSolver::CollideAndSlide || !rb.is_kinematic() {
+            continue;
+        }
+
+        let cast = SlideCast {
+            collider,
+            rotation: rot,
+            filter: SpatialQueryFilter::from_mask(layers.filters),
+            spatial_query: &spatial_query,
+            is_blocking: &|e: Entity| col_parents.get(e)
+            .is_ok_and(|p| blockers.get(p.get())
+                .is_ok_and(|rb| !rb.is_dynamic())
+            )
+        };
+        let mut planes = Vec::<Vec3>::with_capacity(SLIDE_MAX_ITERATIONS * 2);
+        // walking and falling are slid separately, a walk nearly parallel to
+        // the floor it stands on gives unreliable hit normals
+        let motion = translation.0;
+        let mut origin = pos.0;
+        origin = cast.slide(origin, Vec3::new(motion.x, 0.0, motion.z), &mut planes);
+        origin = cast.slide(origin, Vec3::Y * motion.y, &mut planes);
+
+        translation.0 = origin - pos.0;
+        // velocity keeps pointing along the surfaces, not into them
+        for normal in planes.iter() {
+            let into = vel.dot(*normal);
+            if into < 0.0 {
+                vel.0 -= *normal * into;
+            }
+        }
+    }
+}
+
+// depenetration only sees overlaps, fast motion is clamped to the first hit
+// CollideAndSlide characters are always swept
+#[allow(clippy::type_complexity)]
+fn swept_motion_system(
+    mut query: Query<(
+        &RigidBody,
+        &MovementSolver,
+        &ContinuousCollision,
+        &Position,
+        &Rotation,
+        &Collider,
+        &CollisionLayers,
+        &mut AccumulatedTranslation,
+        &mut LinearVelocity
+    ),
+        With<CharacterController>
+    >,
+    blockers: Query<&RigidBody, Without<CharacterController>>,
+    col_parents: Query<&ColliderParent, Without<Sensor>>,
+    spatial_query: Res<SpatialQueryPipeline>
+) {
+    for (
+        rb,
+        solver,
+        ccd,
+        pos,
+        rot,
+        collider,
+        layers,
+        mut translation,
+        mut vel
+    ) in query.iter_mut() {
+        if *solver != MovementSolver::Depenetration
+        || !ccd.enabled
+        || !rb.is_kinematic() {
+            continue;
+        }
+
+        let aabb = collider.aabb(Vec3::ZERO, *rot);
+        let radius = ((aabb.max - aabb.min) * 0.5).min_element();
+        if translation.length() <= radius * ccd.radius_fraction {
+            continue;
+        }
+
+        let cast = SlideCast {
+            collider,
+            rotation: rot,
+            filter: SpatialQueryFilter::from_mask(layers.filters),
+            spatial_query: &spatial_query,
+            is_blocking: &|e: Entity| col_parents.get(e)
+            .is_ok_and(|p| blockers.get(p.get())
+                .is_ok_and(|rb| !rb.is_dynamic())
+            )
+        };
+        let Some((travel, normal)) = cast.sweep(pos.0, translation.0) else {
+            continue;
+        };
+
+        translation.0 = translation.normalize() * travel;
+        let into = vel.dot(normal);
+        if into < 0.0 {
+            vel.0 -= normal * into;
+        }
+    }
+}
+
+struct SlideCast<'a> {
+    collider: &'a Collider,
+    rotation: &'a Rotation,
+    filter: SpatialQueryFilter,
+    spatial_query: &'a SpatialQueryPipeline,
+    is_blocking: &'a dyn Fn(Entity) -> bool
+}
+
+impl<'a> SlideCast<'a> {
+    // first blocking hit along motion, as distance kept before it and its normal
+    fn sweep(&self, origin: Vec3, motion: Vec3) -> Option<(f32, Vec3)> {
+        let dir = Direction3d::new(motion).ok()?;
+        let distance = motion.length();
+        let hit = self.spatial_query.shape_hits(
+            self.collider,
+            origin,
+            self.rotation.0,
+            dir,
+            distance + SLIDE_SKIN,
+            SLIDE_MAX_HITS,
+            true,
+            self.filter.clone()
+        )
+        .into_iter()
+        .find(|hit| (self.is_blocking)(hit.entity))?;
+
+        let travel = (hit.time_of_impact - SLIDE_SKIN).clamp(0.0, distance);
+        // normal2 is on the cast shape, pointing into the hit surface
+        let normal = -self.rotation.rotate(hit.normal2).normalize_or_zero();
+        Some((travel, normal))
+    }
+
+    // returns the end position, hit planes are appended
+    fn slide(&self, mut origin: Vec3, mut remaining: Vec3, planes: &mut Vec<Vec3>) -> Vec3 {
+        let first_plane = planes.len();
+        for _ in 0..SLIDE_MAX_ITERATIONS {
+            let Some((travel, normal)) = self.sweep(origin, remaining) else {
+                return origin + remaining;
+            };
+            let dir = remaining.normalize();
+            origin += dir * travel;
+            remaining -= dir * travel;
+
+            planes.push(normal);
+            remaining = match &planes[first_plane..] {
+                [n] => remaining - *n * remaining.dot(*n),
+                // along the crease of both planes
+                [n1, n2] => {
+                    let crease = n1.cross(*n2).normalize_or_zero();
+                    crease * remaining.dot(crease)
+                }
+                _ => Vec3::ZERO
+            };
+        }
+        origin
+    }
+}
+
+#[allow(clippy::type_complexity)]
 fn kinematic_collisions_system(
     mut ccs: Query<(
         &RigidBody,
         &mut Position,
         &Rotation,
         &mut LinearVelocity,
-        Option<&MaxSlopeAngle>
+        Option<&MaxSlopeAngle>,
+        Option<&CharacterCollision>,
+        Option<&PushStrength>
     ),
         With<CharacterController>
     >,
+    mut bodies: Query<(
+        &RigidBody,
+        &mut LinearVelocity,
+        &Mass
+    ),
+        Without<CharacterController>
+    >,
     col_parents: Query<&ColliderParent, Without<Sensor>>,
     collisions: Res<Collisions>
 ) {
@@ -264,9 +1152,17 @@ fn kinematic_collisions_system(
             continue;
         };
 
+        // both sides are characters, resolve them together
+        if let Ok([chara_1, chara_2]) = ccs.get_many_mut(
+            [col_parent_1.get(), col_parent_2.get()]
+        ) {
+            resolve_character_pair(chara_1, chara_2, &contacts.manifolds);
+            continue;
+        }
+
         let (
             is_first, 
-            (rb, mut pos, rot, mut vel, slope_angle)
+            (rb, mut pos, rot, mut vel, slope_angle, _, push_strength)
         ) = if let Ok(chara) = ccs.get_mut(col_parent_1.get()) {
             (true, chara)
         } else if let Ok(chara) = ccs.get_mut(col_parent_2.get()) {
@@ -274,6 +1170,13 @@ fn kinematic_collisions_system(
         } else {
             continue;
         };
+        let mut other = bodies.get_mut(if is_first {
+            col_parent_2.get()
+        } else {
+            col_parent_1.get()
+        })
+        .ok()
+        .filter(|(rb, ..)| rb.is_dynamic());
 
         if !rb.is_kinematic() {
             continue;
@@ -291,12 +1194,115 @@ fn kinematic_collisions_system(
                 pos.0 += normal * contact.penetration;
             }
 
-            if slope_angle.is_some_and(|angle| {
+            let is_ground = slope_angle.is_some_and(|angle| {
                 normal.angle_between(Vec3::Y)
                 .abs() <= angle.0
-            }) && vel.y < 0.0 {
+            });
+            if is_ground && vel.y < 0.0 {
                 vel.y = vel.y.max(0.0);
             }
+
+            // standing on a body does not push it
+            if let (false, Some((_, other_vel, mass)), Some(push)) = (
+                is_ground,
+                other.as_mut(),
+                push_strength
+            ) {
+                push_body(vel.0, normal, push.0, mass.0, &mut other_vel.0);
+            }
+        }
+    }
+}
+
+// body is pushed along the contact up to character speed into it
+#[inline]
+fn push_body(
+    character_vel: Vec3,
+    normal: Vec3,
+    push_strength: f32,
+    mass: f32,
+    body_vel: &mut Vec3
+) {
+    let dir = -normal;
+    let speed_into = character_vel.dot(dir);
+    if speed_into <= 0.0 {
+        return;
+    }
+
+    let ratio = (push_strength / mass.max(f32::EPSILON)).min(1.0);
+    let target = speed_into * ratio;
+    let current = body_vel.dot(dir);
+    if current < target {
+        *body_vel += dir * (target - current);
+    }
+}
+
+type CharacterItem<'a> = (
+    &'a RigidBody,
+    Mut<'a, Position>,
+    &'a Rotation,
+    Mut<'a, LinearVelocity>,
+    Option<&'a MaxSlopeAngle>,
+    Option<&'a CharacterCollision>,
+    Option<&'a PushStrength>
+);
+
+// separation is split between the two, so neither is treated as static
+fn resolve_character_pair(
+    (rb_1, mut pos_1, rot_1, mut vel_1, slope_angle_1, cc_1, _): CharacterItem,
+    (rb_2, mut pos_2, _, mut vel_2, slope_angle_2, cc_2, _): CharacterItem,
+    manifolds: &[ContactManifold]
+) {
+    let cc_1 = cc_1.copied().unwrap_or_default();
+    let cc_2 = cc_2.copied().unwrap_or_default();
+    if !cc_1.enabled || !cc_2.enabled {
+        return;
+    }
+
+    let (mut share_1, mut share_2) = cc_1.split(&cc_2);
+    // only kinematic bodies are moved here
+    if !rb_1.is_kinematic() {
+        share_1 = 0.0;
+    }
+    if !rb_2.is_kinematic() {
+        share_2 = 0.0;
+    }
+    let stiffness = cc_1.stiffness.min(cc_2.stiffness).clamp(0.0, 1.0);
+    let hard = stiffness >= 1.0;
+
+    for manifold in manifolds.iter() {
+        // pushes character 1 away from character 2
+        let normal = -manifold.global_normal1(rot_1);
+        let depth = manifold.contacts.iter()
+        .map(|c| c.penetration)
+        .fold(0.0, f32::max);
+        if depth <= 0.0 {
+            continue;
+        }
+
+        pos_1.0 += normal * depth * share_1 * stiffness;
+        pos_2.0 -= normal * depth * share_2 * stiffness;
+
+        // hard contact stops walking into each other, soft keeps pushing
+        if hard {
+            if share_1 > 0.0 {
+                let approach = vel_1.dot(normal).min(0.0);
+                vel_1.0 -= normal * approach;
+            }
+            if share_2 > 0.0 {
+                let approach = vel_2.dot(normal).max(0.0);
+                vel_2.0 -= normal * approach;
+            }
+        }
+
+        // standing on top of another character
+        if slope_angle_1.is_some_and(|a| normal.angle_between(Vec3::Y).abs() <= a.0)
+        && vel_1.y < 0.0 {
+            vel_1.y = 0.0;
+        }
+        if slope_angle_2.is_some_and(|a| (-normal).angle_between(Vec3::Y).abs() <= a.0)
+        && vel_2.y < 0.0 {
+            vel_2.y = 0.0;
         }
     }
 }
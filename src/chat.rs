@@ -0,0 +1,441 @@
+// text chat over reliable ordered channel
+// server checks length, rate and filters, then relays by scope
+// client shows recent messages, Enter to compose
+// prefix /r for room, /p for proximity, global otherwise
+
+use std::collections::VecDeque;
+use bevy::{
+    prelude::*,
+    utils::HashMap
+};
+use bevy_replicon::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::{
+    *,
+    network_character_controller::NetworkCharacterController,
+    room::{RoomId, Rooms}
+};
+
+pub const CHAT_MAX_LENGTH: usize = 200;
+pub const CHAT_RATE_BURST: f32 = 5.0;
+// messages per second after burst is spent
+pub const CHAT_RATE_REFILL: f32 = 1.0;
+pub const CHAT_PROXIMITY_RADIUS: f32 = 20.0;
+pub const CHAT_LOG_LINES: usize = 8;
+
+const OPEN_CHAT: KeyCode = KeyCode::Enter;
+const CANCEL_CHAT: KeyCode = KeyCode::Escape;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChatScope {
+    Global,
+    // members of sender's room
+    Room,
+    // members of sender's room within proximity radius
+    Proximity
+}
+
+// client to server
+#[derive(Event, Serialize, Deserialize, Clone)]
+pub struct ChatMessage {
+    pub scope: ChatScope,
+    pub text: String
+}
+
+// server to clients, no sender is a server notice
+#[derive(Event, Serialize, Deserialize, Clone)]
+pub struct ChatBroadcast {
+    pub sender: Option<ClientId>,
+    pub scope: ChatScope,
+    pub text: String
+}
+
+pub enum ChatVerdict {
+    Allow,
+    Replace(String),
+    // reason is sent back to the sender
+    Reject(String)
+}
+
+// moderation hook, filters run in registration order
+pub trait ChatFilter: Send + Sync + 'static {
+    fn check(&self, sender: ClientId, scope: ChatScope, text: &str) -> ChatVerdict;
+}
+
+// masks listed words with '*', case insensitive
+pub struct WordFilter {
+    words: Vec<Vec<char>>
+}
+
+#[inline]
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+impl WordFilter {
+    pub fn new(words: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            words: words.into_iter()
+                .map(|w| w.into().chars().map(fold_case).collect::<Vec<_>>())
+                .filter(|w| !w.is_empty())
+                .collect()
+        }
+    }
+}
+
+impl ChatFilter for WordFilter {
+    fn check(&self, _: ClientId, _: ChatScope, text: &str) -> ChatVerdict {
+        let mut chars = text.chars().collect::<Vec<_>>();
+        let folded = chars.iter().copied().map(fold_case).collect::<Vec<_>>();
+        let mut masked = false;
+        for w in self.words.iter() {
+            for i in 0..folded.len().saturating_sub(w.len() - 1) {
+                if folded[i..i + w.len()] == w[..] {
+                    chars[i..i + w.len()].fill('*');
+                    masked = true;
+                }
+            }
+        }
+
+        if masked {
+            ChatVerdict::Replace(chars.into_iter().collect())
+        } else {
+            ChatVerdict::Allow
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ChatFilters(Vec<Box<dyn ChatFilter>>);
+
+pub trait ChatFilterAppExt {
+    fn add_chat_filter(&mut self, filter: impl ChatFilter) -> &mut Self;
+}
+
+impl ChatFilterAppExt for App {
+    fn add_chat_filter(&mut self, filter: impl ChatFilter) -> &mut Self {
+        self.world.get_resource_or_insert_with(ChatFilters::default)
+        .0.push(Box::new(filter));
+        self
+    }
+}
+
+#[derive(Resource, Clone, Copy)]
+pub struct ChatConfig {
+    pub max_length: usize,
+    pub rate_burst: f32,
+    pub rate_refill: f32,
+    pub proximity_radius: f32
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            max_length: CHAT_MAX_LENGTH,
+            rate_burst: CHAT_RATE_BURST,
+            rate_refill: CHAT_RATE_REFILL,
+            proximity_radius: CHAT_PROXIMITY_RADIUS
+        }
+    }
+}
+
+// token bucket per client
+#[derive(Resource, Default)]
+struct ChatRateLimits(HashMap<ClientId, (f32, f32)>);
+
+impl ChatRateLimits {
+    fn try_take(&mut self, client_id: ClientId, now: f32, config: &ChatConfig) -> bool {
+        let (tokens, last) = self.0.entry(client_id)
+        .or_insert((config.rate_burst, now));
+        *tokens = (*tokens + (now - *last) * config.rate_refill).min(config.rate_burst);
+        *last = now;
+        if *tokens < 1.0 {
+            return false;
+        }
+        *tokens -= 1.0;
+        true
+    }
+}
+
+// control characters are dropped, surrounding spaces trimmed
+#[inline]
+pub fn sanitize_chat_text(text: &str) -> String {
+    text.chars()
+    .filter(|c| !c.is_control())
+    .collect::<String>()
+    .trim()
+    .to_string()
+}
+
+pub struct ChatServerPlugin;
+
+impl Plugin for ChatServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatConfig>()
+        .init_resource::<ChatFilters>()
+        .init_resource::<ChatRateLimits>()
+        .add_systems(PreUpdate, (
+            remove_rate_limit,
+            handle_chat_message
+        ).after(ServerSet::Receive));
+    }
+}
+
+fn remove_rate_limit(
+    mut events: EventReader<ServerEvent>,
+    mut limits: ResMut<ChatRateLimits>
+) {
+    for e in events.read() {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = e {
+            limits.0.remove(client_id);
+        }
+    }
+}
+
+#[inline]
+fn notice(client_id: ClientId, text: String) -> ToClients<ChatBroadcast> {
+    ToClients {
+        mode: SendMode::Direct(client_id),
+        event: ChatBroadcast {
+            sender: None,
+            scope: ChatScope::Global,
+            text
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_chat_message(
+    mut messages: EventReader<FromClient<ChatMessage>>,
+    mut broadcasts: EventWriter<ToClients<ChatBroadcast>>,
+    mut limits: ResMut<ChatRateLimits>,
+    config: Res<ChatConfig>,
+    filters: Res<ChatFilters>,
+    rooms: Res<Rooms>,
+    characters: Query<(&NetworkId, &NetworkCharacterController, &RoomId)>,
+    time: Res<Time<Real>>
+) {
+    for FromClient { client_id, event: message } in messages.read() {
+        let client_id = *client_id;
+        if !limits.try_take(client_id, time.elapsed_seconds(), &config) {
+            broadcasts.send(notice(client_id, "you are sending messages too fast".to_string()));
+            continue;
+        }
+
+        let mut text = sanitize_chat_text(&message.text);
+        if text.is_empty() {
+            continue;
+        }
+        if text.chars().count() > config.max_length {
+            broadcasts.send(notice(
+                client_id,
+                format!("message is longer than {} characters", config.max_length)
+            ));
+            continue;
+        }
+
+        let mut rejected = None;
+        for filter in filters.0.iter() {
+            match filter.check(client_id, message.scope, &text) {
+                ChatVerdict::Allow => (),
+                ChatVerdict::Replace(replaced) => text = replaced,
+                ChatVerdict::Reject(reason) => {
+                    rejected = Some(reason);
+                    break;
+                }
+            }
+        }
+        if let Some(reason) = rejected {
+            broadcasts.send(notice(client_id, format!("message rejected: {reason}")));
+            continue;
+        }
+
+        let event = ChatBroadcast {
+            sender: Some(client_id),
+            scope: message.scope,
+            text
+        };
+        info!("chat: {client_id:?} {:?}: {}", event.scope, event.text);
+
+        if message.scope == ChatScope::Global {
+            broadcasts.send(ToClients { mode: SendMode::Broadcast, event });
+            continue;
+        }
+
+        let Some(room) = rooms.room_of(client_id) else {
+            continue;
+        };
+        let origin = characters.iter()
+        .find(|(net_id, ..)| net_id.client_id() == client_id)
+        .map(|(_, net_cc, _)| net_cc.translation);
+
+        for (net_id, net_cc, room_id) in characters.iter() {
+            if *room_id != room {
+                continue;
+            }
+            if message.scope == ChatScope::Proximity {
+                let in_range = origin.is_some_and(|o| {
+                    o.distance_squared(net_cc.translation) <= config.proximity_radius.powi(2)
+                });
+                if !in_range {
+                    continue;
+                }
+            }
+            broadcasts.send(ToClients {
+                mode: SendMode::Direct(net_id.client_id()),
+                event: event.clone()
+            });
+        }
+    }
+}
+
+// while composing, keyboard is consumed by chat
+#[derive(Resource, Default)]
+pub struct ChatInput {
+    composing: bool,
+    text: String
+}
+
+impl ChatInput {
+    #[inline]
+    pub fn is_composing(&self) -> bool {
+        self.composing
+    }
+}
+
+#[derive(Resource, Default)]
+struct ChatLog(VecDeque<String>);
+
+#[derive(Component)]
+struct ChatText;
+
+pub struct ChatClientPlugin;
+
+impl Plugin for ChatClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatInput>()
+        .init_resource::<ChatLog>()
+        .add_systems(Startup, setup_chat_ui)
+        .add_systems(PreUpdate, (
+            compose_chat_system
+            .after(bevy::input::InputSystem),
+            receive_chat_system
+            .after(ClientSet::Receive)
+        ))
+        .add_systems(Update, update_chat_ui);
+    }
+}
+
+fn setup_chat_ui(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle {
+            font_size: 18.0,
+            color: Color::WHITE,
+            ..default()
+        })
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            ..default()
+        }),
+        ChatText
+    ));
+}
+
+#[inline]
+fn parse_chat_input(text: &str) -> ChatMessage {
+    let (scope, text) = if let Some(t) = text.strip_prefix("/r ") {
+        (ChatScope::Room, t)
+    } else if let Some(t) = text.strip_prefix("/p ") {
+        (ChatScope::Proximity, t)
+    } else {
+        (ChatScope::Global, text)
+    };
+    ChatMessage {
+        scope,
+        text: text.to_string()
+    }
+}
+
+fn compose_chat_system(
+    mut input: ResMut<ChatInput>,
+    mut keyboard: ResMut<ButtonInput<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut messages: EventWriter<ChatMessage>
+) {
+    if !input.composing {
+        characters.clear();
+        if keyboard.just_pressed(OPEN_CHAT) {
+            input.composing = true;
+            keyboard.reset_all();
+        }
+        return;
+    }
+
+    for c in characters.read() {
+        input.text.extend(c.char.chars().filter(|c| !c.is_control()));
+    }
+    if keyboard.just_pressed(KeyCode::Backspace) {
+        input.text.pop();
+    }
+    if keyboard.just_pressed(CANCEL_CHAT) {
+        input.composing = false;
+        input.text.clear();
+    } else if keyboard.just_pressed(OPEN_CHAT) {
+        input.composing = false;
+        let text = std::mem::take(&mut input.text);
+        if !sanitize_chat_text(&text).is_empty() {
+            messages.send(parse_chat_input(&text));
+        }
+    }
+
+    // movement and other bindings do not see typed keys
+    keyboard.reset_all();
+}
+
+fn receive_chat_system(
+    mut broadcasts: EventReader<ChatBroadcast>,
+    mut log: ResMut<ChatLog>
+) {
+    for b in broadcasts.read() {
+        let line = match b.sender {
+            Some(sender) => {
+                let scope = match b.scope {
+                    ChatScope::Global => "",
+                    ChatScope::Room => "[room] ",
+                    ChatScope::Proximity => "[near] "
+                };
+                format!("{scope}{}: {}", sender.get(), b.text)
+            }
+            None => format!("* {}", b.text)
+        };
+        info!("chat: {line}");
+        log.0.push_back(line);
+        while log.0.len() > CHAT_LOG_LINES {
+            log.0.pop_front();
+        }
+    }
+}
+
+fn update_chat_ui(
+    mut query: Query<&mut Text, With<ChatText>>,
+    input: Res<ChatInput>,
+    log: Res<ChatLog>
+) {
+    if !input.is_changed() && !log.is_changed() {
+        return;
+    }
+    let Ok(mut text) = query.get_single_mut() else {
+        return;
+    };
+
+    let mut s = log.0.iter()
+    .cloned()
+    .collect::<Vec<_>>()
+    .join("\n");
+    if input.composing {
+        s.push_str(&format!("\n> {}_", input.text));
+    }
+    text.sections[0].value = s;
+}
@@ -0,0 +1,480 @@
+// records replicated world as received from server
+// playback feeds the demo back without any server
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path
+};
+use bevy::{
+    prelude::*,
+    input::mouse::MouseMotion,
+    utils::HashMap
+};
+use bevy_replicon::{
+    prelude::*,
+    bincode::{self, DefaultOptions, Options}
+};
+use serde::{Serialize, Deserialize};
+use crate::{
+    *,
+    level::*,
+    network_character_controller::NetworkCharacterController
+};
+
+const DEMO_MAGIC: [u8; 4] = *b"NCDM";
+const DEMO_VERSION: u16 = 1;
+
+pub const DEMO_SEEK_STEP: f32 = 5.0;
+pub const DEMO_MIN_SPEED: f32 = 0.125;
+pub const DEMO_MAX_SPEED: f32 = 4.0;
+pub const FREE_CAMERA_SPEED: f32 = 10.0;
+pub const FREE_CAMERA_FAST_SCALE: f32 = 4.0;
+pub const FREE_CAMERA_SENSITIVITY: f32 = 0.003;
+
+const PAUSE: KeyCode = KeyCode::Space;
+const SEEK_BACK: KeyCode = KeyCode::ArrowLeft;
+const SEEK_FORWARD: KeyCode = KeyCode::ArrowRight;
+const SPEED_UP: KeyCode = KeyCode::ArrowUp;
+const SLOW_DOWN: KeyCode = KeyCode::ArrowDown;
+const RESTART: KeyCode = KeyCode::Home;
+
+const CAMERA_FORWARD: KeyCode = KeyCode::KeyW;
+const CAMERA_LEFT: KeyCode = KeyCode::KeyA;
+const CAMERA_BACK: KeyCode = KeyCode::KeyS;
+const CAMERA_RIGHT: KeyCode = KeyCode::KeyD;
+const CAMERA_UP: KeyCode = KeyCode::KeyE;
+const CAMERA_DOWN: KeyCode = KeyCode::KeyQ;
+const CAMERA_FAST: KeyCode = KeyCode::ShiftLeft;
+const CAMERA_LOOK: MouseButton = MouseButton::Right;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum DemoEvent {
+    Spawned {
+        entity: u64,
+        client_id: ClientId,
+        translation: Vec3,
+        yaw: f32
+    },
+    Updated {
+        entity: u64,
+        translation: Vec3,
+        yaw: f32
+    },
+    Despawned {
+        entity: u64
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DemoEntry {
+    // seconds since recording started
+    pub time: f32,
+    pub event: DemoEvent
+}
+
+pub fn read_demo<R: Read>(mut reader: R) -> anyhow::Result<Vec<DemoEntry>> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != DEMO_MAGIC {
+        anyhow::bail!("not a client demo");
+    }
+    let mut version = [0u8; 2];
+    reader.read_exact(&mut version)?;
+    let version = u16::from_le_bytes(version);
+    if version != DEMO_VERSION {
+        anyhow::bail!("unsupported demo version: {version}");
+    }
+
+    let mut entries = Vec::new();
+    loop {
+        match DefaultOptions::new().deserialize_from(&mut reader) {
+            Ok(entry) => entries.push(entry),
+            Err(e) if matches!(
+                e.as_ref(),
+                bincode::ErrorKind::Io(io) if io.kind() == ErrorKind::UnexpectedEof
+            ) => break,
+            Err(e) => return Err(e.into())
+        }
+    }
+    Ok(entries)
+}
+
+#[derive(Resource)]
+pub struct DemoRecorder {
+    writer: BufWriter<File>,
+    start: Option<f32>
+}
+
+impl DemoRecorder {
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&DEMO_MAGIC)?;
+        writer.write_all(&DEMO_VERSION.to_le_bytes())?;
+        Ok(Self {
+            writer,
+            start: None
+        })
+    }
+
+    #[inline]
+    fn write(&mut self, elapsed: f32, event: DemoEvent) {
+        let start = *self.start.get_or_insert(elapsed);
+        let entry = DemoEntry {
+            time: elapsed - start,
+            event
+        };
+        if let Err(e) = DefaultOptions::new().serialize_into(&mut self.writer, &entry) {
+            error!("failed to write demo: {e}");
+        }
+    }
+}
+
+// insert DemoRecorder resource to start recording
+pub struct DemoRecorderPlugin;
+
+impl Plugin for DemoRecorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate,
+            record_demo_system
+            .after(ClientSet::Receive)
+            .run_if(resource_exists::<DemoRecorder>)
+        )
+        .add_systems(Last,
+            flush_demo_system
+            .run_if(resource_exists::<DemoRecorder>)
+        );
+    }
+}
+
+fn record_demo_system(
+    mut recorder: ResMut<DemoRecorder>,
+    query: Query<(
+        Entity,
+        &NetworkId,
+        Ref<NetworkCharacterController>
+    ),
+        Changed<NetworkCharacterController>
+    >,
+    mut removed: RemovedComponents<NetworkId>,
+    time: Res<Time>
+) {
+    let elapsed = time.elapsed_seconds();
+
+    for (e, net_id, net_cc) in query.iter() {
+        let event = if net_cc.is_added() {
+            DemoEvent::Spawned {
+                entity: e.to_bits(),
+                client_id: net_id.client_id(),
+                translation: net_cc.translation,
+                yaw: net_cc.yaw
+            }
+        } else {
+            DemoEvent::Updated {
+                entity: e.to_bits(),
+                translation: net_cc.translation,
+                yaw: net_cc.yaw
+            }
+        };
+        recorder.write(elapsed, event);
+    }
+
+    for e in removed.read() {
+        recorder.write(elapsed, DemoEvent::Despawned { entity: e.to_bits() });
+    }
+}
+
+fn flush_demo_system(mut recorder: ResMut<DemoRecorder>) {
+    if let Err(e) = recorder.writer.flush() {
+        error!("failed to flush demo: {e}");
+    }
+}
+
+#[derive(Resource)]
+pub struct DemoPlayback {
+    entries: Vec<DemoEntry>,
+    cursor: usize,
+    time: f32,
+    seek_to: Option<f32>,
+    speed: f32,
+    paused: bool,
+    entities: HashMap<u64, Entity>
+}
+
+impl DemoPlayback {
+    pub fn new(entries: Vec<DemoEntry>) -> Self {
+        Self {
+            entries,
+            cursor: 0,
+            time: 0.0,
+            seek_to: None,
+            speed: 1.0,
+            paused: false,
+            entities: default()
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self::new(read_demo(BufReader::new(file))?))
+    }
+
+    #[inline]
+    pub fn duration(&self) -> f32 {
+        self.entries.last()
+        .map_or(0.0, |entry| entry.time)
+    }
+
+    #[inline]
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    #[inline]
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    #[inline]
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(DEMO_MIN_SPEED, DEMO_MAX_SPEED);
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    #[inline]
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    #[inline]
+    pub fn seek(&mut self, time: f32) {
+        self.seek_to = Some(time.clamp(0.0, self.duration()));
+    }
+}
+
+#[derive(Resource)]
+struct DemoAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>
+}
+
+#[derive(Component, Default)]
+pub struct FreeCamera {
+    yaw: f32,
+    pitch: f32
+}
+
+// insert DemoPlayback resource to play
+pub struct DemoPlaybackPlugin;
+
+impl Plugin for DemoPlaybackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, (
+            setup_light,
+            setup_free_camera,
+            setup_demo_assets,
+            client_setup_floor,
+            client_setup_box
+        ))
+        .add_systems(Update, (
+            playback_control_system,
+            playback_system
+        ).chain(
+        ).run_if(resource_exists::<DemoPlayback>))
+        .add_systems(Update, free_camera_system);
+    }
+}
+
+fn setup_demo_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    commands.insert_resource(DemoAssets {
+        mesh: meshes.add(Mesh::from(
+            Capsule3d::new(CHARACTER_RADIUS, CHARACTER_HIGHT * 0.5)
+        )),
+        material: materials.add(CHARACTER_COLOR)
+    });
+}
+
+fn setup_free_camera(mut commands: Commands) {
+    let transform = Transform::from_translation(CAMERA_POSITION)
+        .looking_at(Vec3::ZERO, Vec3::Y);
+    let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+
+    commands.spawn((
+        Camera3dBundle{
+            transform,
+            ..default()
+        },
+        FreeCamera{ yaw, pitch }
+    ));
+}
+
+fn playback_control_system(
+    mut playback: ResMut<DemoPlayback>,
+    keyboard: Res<ButtonInput<KeyCode>>
+) {
+    if keyboard.just_pressed(PAUSE) {
+        let paused = !playback.is_paused();
+        playback.set_paused(paused);
+    }
+    if keyboard.just_pressed(SEEK_BACK) {
+        let time = playback.time() - DEMO_SEEK_STEP;
+        playback.seek(time);
+    }
+    if keyboard.just_pressed(SEEK_FORWARD) {
+        let time = playback.time() + DEMO_SEEK_STEP;
+        playback.seek(time);
+    }
+    if keyboard.just_pressed(RESTART) {
+        playback.seek(0.0);
+    }
+    if keyboard.just_pressed(SPEED_UP) {
+        let speed = playback.speed() * 2.0;
+        playback.set_speed(speed);
+    }
+    if keyboard.just_pressed(SLOW_DOWN) {
+        let speed = playback.speed() * 0.5;
+        playback.set_speed(speed);
+    }
+    if keyboard.get_just_pressed().next().is_some() {
+        info!(
+            "demo: {:.2}/{:.2} speed: {} paused: {}",
+            playback.time(),
+            playback.duration(),
+            playback.speed(),
+            playback.is_paused()
+        );
+    }
+}
+
+fn playback_system(
+    mut commands: Commands,
+    mut playback: ResMut<DemoPlayback>,
+    assets: Res<DemoAssets>,
+    time: Res<Time>
+) {
+    let playback = playback.as_mut();
+
+    let target = match playback.seek_to.take() {
+        Some(target) => {
+            if target < playback.time {
+                // state is only known by applying events from the start
+                for (_, e) in playback.entities.drain() {
+                    commands.entity(e).despawn_recursive();
+                }
+                playback.cursor = 0;
+            }
+            target
+        }
+        None if !playback.paused => {
+            (playback.time + time.delta_seconds() * playback.speed)
+            .min(playback.duration())
+        }
+        None => return
+    };
+    playback.time = target;
+
+    while let Some(entry) = playback.entries.get(playback.cursor) {
+        if entry.time > playback.time {
+            break;
+        }
+        playback.cursor += 1;
+
+        match entry.event {
+            DemoEvent::Spawned { entity, client_id, translation, yaw } => {
+                let e = commands.spawn((
+                    NetworkId::new(client_id),
+                    NetworkCharacterController{ translation, yaw, ..default() },
+                    PbrBundle{
+                        mesh: assets.mesh.clone(),
+                        material: assets.material.clone(),
+                        transform: Transform{
+                            translation,
+                            rotation: yaw_to_quat(yaw),
+                            ..default()
+                        },
+                        ..default()
+                    }
+                )).id();
+                playback.entities.insert(entity, e);
+            }
+            DemoEvent::Updated { entity, translation, yaw } => {
+                let Some(&e) = playback.entities.get(&entity) else {
+                    continue;
+                };
+                commands.entity(e)
+                .insert((
+                    NetworkCharacterController{ translation, yaw, ..default() },
+                    Transform{
+                        translation,
+                        rotation: yaw_to_quat(yaw),
+                        ..default()
+                    }
+                ));
+            }
+            DemoEvent::Despawned { entity } => {
+                if let Some(e) = playback.entities.remove(&entity) {
+                    commands.entity(e).despawn_recursive();
+                }
+            }
+        }
+    }
+}
+
+fn free_camera_system(
+    mut query: Query<(&mut FreeCamera, &mut Transform)>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    mut mouse: EventReader<MouseMotion>,
+    time: Res<Time<Real>>
+) {
+    let Ok((mut camera, mut transform)) = query.get_single_mut() else {
+        return;
+    };
+
+    if mouse_buttons.pressed(CAMERA_LOOK) {
+        for e in mouse.read() {
+            camera.yaw -= e.delta.x * FREE_CAMERA_SENSITIVITY;
+            camera.pitch = (camera.pitch - e.delta.y * FREE_CAMERA_SENSITIVITY)
+            .clamp(-std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_2);
+        }
+    } else {
+        mouse.clear();
+    }
+    transform.rotation = Quat::from_euler(EulerRot::YXZ, camera.yaw, camera.pitch, 0.0);
+
+    let mut dir = Vec3::ZERO;
+    if keyboard.pressed(CAMERA_FORWARD) {
+        dir += *transform.forward();
+    }
+    if keyboard.pressed(CAMERA_BACK) {
+        dir -= *transform.forward();
+    }
+    if keyboard.pressed(CAMERA_RIGHT) {
+        dir += *transform.right();
+    }
+    if keyboard.pressed(CAMERA_LEFT) {
+        dir -= *transform.right();
+    }
+    if keyboard.pressed(CAMERA_UP) {
+        dir += Vec3::Y;
+    }
+    if keyboard.pressed(CAMERA_DOWN) {
+        dir -= Vec3::Y;
+    }
+
+    let speed = if keyboard.pressed(CAMERA_FAST) {
+        FREE_CAMERA_SPEED * FREE_CAMERA_FAST_SCALE
+    } else {
+        FREE_CAMERA_SPEED
+    };
+    // camera keeps moving at real speed while demo is paused or slowed
+    transform.translation += dir.normalize_or_zero() * speed * time.delta_seconds();
+}
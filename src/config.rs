@@ -1,3 +1,4 @@
+use std::ops::Range;
 use bevy::utils::{SystemTime, Uuid};
 
 pub const DEV_SERVER_TICK_RATE: f32 = 20.0;
@@ -26,6 +27,10 @@ pub const ROTATION_ERROR_THRESHOLD: f32 = 1.0;
 // 1sec / network tick
 pub const PREDICTION_ERROR_COUNT_THRESHOLD: u32 = 10;
 
+// netcode user data layout
+pub const USER_DATA_SESSION_ID: Range<usize> = 0..16;
+pub const USER_DATA_ROOM_ID: Range<usize> = 16..20;
+
 pub const DISTANCE_CULLING_THREASHOLD: f32 = 100.0;
 
 pub const PHYSICS_FIXED_TICK_RATE: f32 = 64.0;
@@ -65,14 +70,23 @@ pub fn get_dev_client_id() -> u64 {
     }
 }
 
-pub fn get_dev_user_data() -> [u8; 256] {
+// random session when none is given, only accepted without authentication
+pub fn get_dev_user_data(room_id: u32, session_id: Option<Uuid>) -> [u8; 256] {
     if cfg!(debug_assertions) {
         // this will be session id generated by backend service
         let mut user_data = [0u8; 256];
-        user_data[0..16].copy_from_slice(Uuid::new_v4().as_bytes());
+        let session_id = session_id.unwrap_or_else(Uuid::new_v4);
+        user_data[USER_DATA_SESSION_ID].copy_from_slice(session_id.as_bytes());
+        user_data[USER_DATA_ROOM_ID].copy_from_slice(&room_id.to_le_bytes());
         user_data
     } else {
         panic!("do not use dev user data")
     }
 }
 
+// value following the flag, e.g. --record <path>
+pub fn get_arg_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args();
+    args.position(|a| a == flag)?;
+    args.next()
+}
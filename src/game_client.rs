@@ -6,36 +6,53 @@ use crate::{
     level::*,
     client_builder::Client,
     network_character_controller::*,
+    camera::FollowCameraPlugin,
+    input_mapping::*,
+    chat::ChatClientPlugin,
+    spawn::CharacterRespawned,
+    props::PropClientPlugin,
+    interaction::InteractionClientPlugin,
+    health::{HealthClientPlugin, Dead},
+    projectile::ProjectileClientPlugin,
+    pawn::*
 };
 
-const FORWARD: KeyCode = KeyCode::KeyW;
-const LEFT: KeyCode = KeyCode::KeyA;
-const BACK: KeyCode = KeyCode::KeyS;
-const RIGHT: KeyCode = KeyCode::KeyD;
-const JUMP: KeyCode = KeyCode::Space;
+#[derive(Component)]
+pub struct LocalCharacter;
 
 pub struct GameClientPlugin;
 
 impl Plugin for GameClientPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(PhysicsDebugPlugin::default())
+        app.add_plugins((
+            PhysicsDebugPlugin::default(),
+            InputMappingPlugin,
+            FollowCameraPlugin,
+            ChatClientPlugin,
+            PropClientPlugin,
+            InteractionClientPlugin,
+            HealthClientPlugin,
+            ProjectileClientPlugin
+        ))
         .add_systems(Startup, (
             setup_light,
-            setup_fixed_camera,
             client_setup_floor,
-            client_setup_box
+            client_setup_box,
+            client_setup_water,
+            client_setup_ladder
         ))
         .add_systems(PreUpdate, (
             monitor_connection_system,
             handle_player_spawn,
+            handle_respawn,
+            sync_remote_movement_state_system,
             draw_net_cc_gizmos_system
         ).chain(
         ).after(ClientSet::Receive))
-        .add_systems(FixedUpdate, (
-            handle_input,
-            handle_action
-        ).chain(
-        ).before(BEFORE_PHYSICS_SET));
+        .add_systems(FixedUpdate,
+            handle_input
+            .in_set(PawnSet::Input)
+        );
     }
 }
 
@@ -81,6 +98,7 @@ fn handle_player_spawn(
         if client.id() == net_id.client_id().get() {
             commands.entity(e)
             .insert((
+                LocalCharacter,
                 InstantEventBuffer::<NetworkAction>::new(),
                 LockedAxes::new().lock_rotation_x().lock_rotation_z(),
                 CharacterControllerBundle::new(
@@ -100,75 +118,103 @@ fn handle_player_spawn(
     }
 }
 
-fn handle_input(
-    mut query: Query<&mut InstantEventBuffer<NetworkAction>>, 
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mut mouse: EventReader<MouseMotion>,
-    mut net_actions: EventWriter<NetworkAction>
+// predicted character would keep falling until corrected
+fn handle_respawn(
+    mut query: Query<(
+        &NetworkId,
+        &mut Position,
+        &mut LinearVelocity
+    ), With<LocalCharacter>>,
+    mut events: EventReader<CharacterRespawned>
 ) {
-    let Ok(ref mut actions) = query.get_single_mut() else {
+    let Ok((net_id, mut pos, mut vel)) = query.get_single_mut() else {
+        events.clear();
         return;
     };
 
-    let mut action = NetworkAction::default();
-
-    if keyboard.pressed(FORWARD) {
-        action.linear.y += 1.0;
-    }
-    if keyboard.pressed(BACK) {
-        action.linear.y -= 1.0;
-    }
-    if keyboard.pressed(RIGHT) {
-        action.linear.x += 1.0;
-    }
-    if keyboard.pressed(LEFT) {
-        action.linear.x -= 1.0;
-    }
-    if keyboard.just_pressed(JUMP) {
-        action.jump = true;
+    for CharacterRespawned { client_id, position } in events.read() {
+        if *client_id == net_id.client_id() {
+            pos.0 = *position;
+            vel.0 = Vec3::ZERO;
+            info!("respawned at: {position}");
+        }
     }
+}
 
-    for e in mouse.read() {
-        action.angular += e.delta;
+// local character detects swimming and climbing itself
+#[allow(clippy::type_complexity)]
+fn sync_remote_movement_state_system(
+    mut commands: Commands,
+    query: Query<(
+        Entity,
+        &NetworkCharacterController,
+        Has<Swimming>,
+        Has<Climbing>
+    ), (
+        Changed<NetworkCharacterController>,
+        Without<LocalCharacter>
+    )>
+) {
+    for (e, net_cc, is_swimming, is_climbing) in query.iter() {
+        if net_cc.swimming && !is_swimming {
+            commands.entity(e)
+            .insert(Swimming);
+        } else if !net_cc.swimming && is_swimming {
+            commands.entity(e)
+            .remove::<Swimming>();
+        }
+        if net_cc.climbing && !is_climbing {
+            commands.entity(e)
+            .insert(Climbing);
+        } else if !net_cc.climbing && is_climbing {
+            commands.entity(e)
+            .remove::<Climbing>();
+        }
     }
-
-    actions.send(action.clone());
-    net_actions.send(action);
 }
 
-fn handle_action(
-    mut query: Query<(
-        &mut InstantEventBuffer<NetworkAction>,
-        &mut InstantEventBuffer<ControllerAction>
-    )>
+fn handle_input(
+    mut actions: PawnInputWriter<CharacterPawn>,
+    input: ActionInput,
+    mut mouse: EventReader<MouseMotion>
 ) {
-    let Ok((
-        ref mut actions, 
-        ref mut controls
-    )) = query.get_single_mut() else {
+    if !actions.has_local_pawn() {
         return;
+    }
+
+    let mut action = NetworkAction{
+        linear: input.linear(),
+        angular: input.look_stick(),
+        jump: input.just_pressed(InputAction::Jump),
+        vertical: input.vertical()
     };
 
-    for a in actions.read() {
-        if a.linear != Vec2::ZERO {
-            controls.send(ControllerAction::Move(a.linear.normalize()));
-        }
-        if a.jump {
-            controls.send(ControllerAction::Jump);
-        }
+    for e in mouse.read() {
+        action.angular += e.delta * input.map().mouse_sensitivity;
     }
+
+    actions.send(action);
 }
 
 fn draw_net_cc_gizmos_system(
-    query: Query<&NetworkCharacterController>,
+    query: Query<(&NetworkCharacterController, Has<Dead>)>,
     mut gizmos: Gizmos
 ) {
-    for net_cc in query.iter() {
+    for (net_cc, is_dead) in query.iter() {
+        let color = if is_dead {
+            Color::GRAY
+        } else if net_cc.swimming {
+            Color::CYAN
+        } else if net_cc.climbing {
+            Color::ORANGE
+        } else {
+            Color::GREEN
+        };
         gizmos.cuboid(Transform{
             translation: net_cc.translation,
             rotation: yaw_to_quat(net_cc.yaw),
             ..default()
-        }, Color::GREEN);
+        }, color);
 
         // info!(
         //     "trans: {} : rot: {}", 
@@ -1,101 +1,182 @@
 use character_controller::CharacterControllerBundle;
-use instant_event_buffer::InstantEventBuffer;
+use bevy_replicon_renet::renet::{
+    self,
+    RenetServer,
+    transport::NetcodeServerTransport
+};
 
 use crate::{
     *,
-    level::*,
     network_character_controller::*,
-    character_controller::*
+    pawn::NetworkPawnBundle,
+    room::*,
+    user_data::*,
+    chat::ChatServerPlugin,
+    spawn::*,
+    props::PropServerPlugin,
+    interaction::InteractionServerPlugin,
+    health::*,
+    projectile::ProjectileServerPlugin,
+    auth::*,
+    access_list::*,
+    admin_console::AdminSimulationPlugin
 };
 
+// authenticated and not banned, characters are spawned from this
+// and the recorder records the connection from it
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ClientAdmitted {
+    pub client_id: ClientId,
+    pub account_id: Option<AccountId>
+}
+
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct AdmissionSet;
+
 pub struct GameServerPlugin;
 
 impl Plugin for GameServerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, (
-            server_setup_floor,
-            server_setup_box
+        app.add_plugins((
+            ConnectionUserDataPlugin,
+            AuthPlugin,
+            RoomPlugin,
+            ChatServerPlugin,
+            SpawnPlugin,
+            PropServerPlugin,
+            InteractionServerPlugin,
+            HealthServerPlugin,
+            ProjectileServerPlugin,
+            AccessListPlugin,
+            AdminSimulationPlugin
         ))
+        .add_event::<ClientAdmitted>()
+        .add_systems(PreUpdate,
+            admit_client
+            .in_set(AdmissionSet)
+            .after(AuthSet)
+        )
         .add_systems(PreUpdate, 
             handle_server_event
             .after(ServerSet::Receive)
-        )
-        .add_systems(FixedUpdate, 
-            handle_action
-            .before(BEFORE_PHYSICS_SET)
-        )
-        .add_systems(PostUpdate, 
-            handle_character_controller_output
-            .before(ServerSet::Send)
+            .after(AdmissionSet)
+            .after(RoomSet)
         );
     }
 }
 
-fn handle_server_event(
-    mut commands: Commands,
-    mut events: EventReader<ServerEvent>
+// account bans are only known after authentication,
+// so every key is checked here
+fn admit_client(
+    mut authenticated: EventReader<ClientAuthenticated>,
+    mut admitted: EventWriter<ClientAdmitted>,
+    access: Res<AccessList>,
+    user_data: Res<ConnectionUserData>,
+    transport: Option<Res<NetcodeServerTransport>>,
+    mut renet: Option<ResMut<RenetServer>>
 ) {
-    for e in events.read() {
-        match e {
-            ServerEvent::ClientConnected { client_id } => {
-                commands.spawn((
-                    Replicated,
-                    NetworkId::new(*client_id),
-                    TransformBundle::from_transform(
-                        Transform::from_translation(CHARACTER_SPAWN_POSITION)
-                    ),
-                    LockedAxes::new().lock_rotation_x().lock_rotation_z(),
-                    CharacterControllerBundle::new(
-                        Collider::capsule(CHARACTER_HIGHT, CHARACTER_RADIUS),
-                        GRAVITY
-                    ),
-                    NetworkCharacterController{
-                        translation: CHARACTER_SPAWN_POSITION,
-                        ..default()
-                    }
-                ));
-
-                info!("client: {client_id:?} connected");
+    let now = unix_now();
+    for ClientAuthenticated { client_id, account_id } in authenticated.read() {
+        let keys = connection_keys(
+            *client_id,
+            *account_id,
+            &user_data,
+            transport.as_deref()
+        );
+        match access.check(&keys, now) {
+            Ok(()) => {
+                admitted.send(ClientAdmitted {
+                    client_id: *client_id,
+                    account_id: *account_id
+                });
             }
-            ServerEvent::ClientDisconnected { client_id, reason } => {
-                info!("client: {client_id:?} disconnected with reason: {reason}");
+            Err(reason) => {
+                warn!("client: {client_id:?} refused: {reason}");
+                if let Some(renet) = renet.as_deref_mut() {
+                    renet.disconnect(renet::ClientId::from_raw(client_id.get()));
+                }
             }
         }
     }
 }
 
-fn handle_action(
-    mut query: Query<(
-        &NetworkId,
-        &mut InstantEventBuffer<ControllerAction>
-    )>,
-    mut actions: EventReader<FromClient<NetworkAction>>,
+#[allow(clippy::too_many_arguments)]
+fn handle_server_event(
+    mut commands: Commands,
+    mut events: EventReader<ServerEvent>,
+    mut admitted: EventReader<ClientAdmitted>,
+    mut rooms: ResMut<Rooms>,
+    user_data: Res<ConnectionUserData>,
+    characters: Query<(Entity, &NetworkId)>,
+    placed: Query<(&Transform, &RoomId), With<NetworkId>>,
+    mut selector: SpawnSelector
 ) {
-    for FromClient { client_id, event: action } in actions.read() {
-        for (net_id, mut controls) in query.iter_mut() {
-            if *client_id == net_id.client_id() {
-                if action.linear != Vec2::ZERO {
-                    controls.send(ControllerAction::Move(action.linear.normalize()));
-                }
-                if action.jump {
-                    controls.send(ControllerAction::Jump);
+    // characters spawned in this frame are not in the query yet,
+    // so clients gone in this frame are not spawned at all
+    let disconnected = events.read()
+    .filter_map(|e| match e {
+        ServerEvent::ClientDisconnected { client_id, reason } => Some((*client_id, reason)),
+        ServerEvent::ClientConnected { .. } => None
+    })
+    .collect::<Vec<_>>();
+    let mut spawned = Vec::<(RoomId, Vec3)>::new();
+    // connected clients get a character once admitted
+    for ClientAdmitted { client_id, account_id } in admitted.read() {
+        if disconnected.iter().any(|(id, _)| id == client_id) {
+            continue;
+        }
+        let requested = user_data.get(*client_id)
+        .map_or(DEFAULT_ROOM, |u| RoomId(u.room_id()));
+        let room = rooms.join(*client_id, requested);
+        let layers = rooms.layers(room)
+        .unwrap_or_default();
+        let others = placed.iter()
+        .filter(|(_, r)| **r == room)
+        .map(|(t, _)| t.translation)
+        .chain(spawned.iter()
+            .filter(|(r, _)| *r == room)
+            .map(|(_, p)| *p)
+        )
+        .collect::<Vec<_>>();
+        let position = selector.select(room, layers, &others);
+        spawned.push((room, position));
+
+        let mut character = commands.spawn((
+            Replicated,
+            NetworkId::new(*client_id),
+            room,
+            TransformBundle::from_transform(
+                Transform::from_translation(position)
+            ),
+            Health::new(CHARACTER_MAX_HEALTH),
+            LockedAxes::new().lock_rotation_x().lock_rotation_z(),
+            CharacterControllerBundle::new(
+                Collider::capsule(CHARACTER_HIGHT, CHARACTER_RADIUS),
+                GRAVITY
+            ).with_collision_layers(layers),
+            NetworkPawnBundle::new(
+                CharacterPawn,
+                NetworkCharacterController{
+                    translation: position,
+                    ..default()
                 }
-            }
+            )
+        ));
+        if let Some(account_id) = account_id {
+            character.insert(*account_id);
         }
+
+        info!("client: {client_id:?} connected to room: {room:?} at: {position}");
     }
-}
 
-fn handle_character_controller_output(
-    mut query: Query<(
-        &Transform,
-        &mut NetworkCharacterController
-    )>
-) {
-    for (transform, mut net_cc) in query.iter_mut() {
-        let trans = transform.translation;
-        let yaw = quat_to_yaw(transform.rotation);
+    for (client_id, reason) in disconnected {
+        rooms.leave(client_id);
+        for (e, net_id) in characters.iter() {
+            if net_id.client_id() == client_id {
+                commands.entity(e).despawn_recursive();
+            }
+        }
 
-        net_cc.translation = trans;
-        net_cc.yaw = yaw;
+        info!("client: {client_id:?} disconnected with reason: {reason}");
     }
 }
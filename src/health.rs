@@ -0,0 +1,274 @@
+// character health, damage, death and respawn
+// damage is applied on server in fixed ticks, so replay reproduces it
+// dead characters ignore input and stay where they fell until the respawn timer ends,
+// then respawn at a point picked like out of bounds respawn
+
+use std::time::Duration;
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::{
+    *,
+    character_controller::{CharacterLanded, FallTracker},
+    room::{RoomId, Rooms},
+    spawn::{SpawnSelector, CharacterRespawned}
+};
+
+pub const CHARACTER_MAX_HEALTH: f32 = 100.0;
+pub const RESPAWN_DELAY: f32 = 3.0;
+// landing slower than this is harmless, a little above a fall from a box
+pub const FALL_DAMAGE_MIN_SPEED: f32 = 15.0;
+// per unit of landing speed above the minimum
+pub const FALL_DAMAGE_PER_SPEED: f32 = 8.0;
+
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32
+}
+
+impl Health {
+    #[inline]
+    pub fn new(max: f32) -> Self {
+        Self {
+            current: max,
+            max
+        }
+    }
+
+    #[inline]
+    pub fn is_alive(&self) -> bool {
+        self.current > 0.0
+    }
+
+    #[inline]
+    pub fn fraction(&self) -> f32 {
+        if self.max > 0.0 {
+            (self.current / self.max).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+// replicated, input is ignored until respawn
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Default)]
+#[component(storage = "SparseSet")]
+pub struct Dead;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DamageSource {
+    Fall,
+    // level hazards and server commands
+    Environment,
+    Client(ClientId)
+}
+
+// server side, amounts <= 0 and damage to dead characters are ignored
+#[derive(Event, Clone, Copy, Debug)]
+pub struct Damage {
+    pub target: Entity,
+    pub amount: f32,
+    pub source: DamageSource
+}
+
+// server to clients
+#[derive(Event, Serialize, Deserialize, Clone, Copy)]
+pub struct CharacterDied {
+    pub client_id: ClientId,
+    pub source: DamageSource
+}
+
+// server side, in fixed time
+#[derive(Component)]
+pub struct RespawnTimer(Timer);
+
+#[derive(Resource, Clone, Copy)]
+pub struct HealthConfig {
+    pub respawn_delay: f32,
+    pub fall_damage_min_speed: f32,
+    pub fall_damage_per_speed: f32
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            respawn_delay: RESPAWN_DELAY,
+            fall_damage_min_speed: FALL_DAMAGE_MIN_SPEED,
+            fall_damage_per_speed: FALL_DAMAGE_PER_SPEED
+        }
+    }
+}
+
+impl HealthConfig {
+    #[inline]
+    pub fn fall_damage(&self, speed: f32) -> f32 {
+        (speed - self.fall_damage_min_speed).max(0.0) * self.fall_damage_per_speed
+    }
+}
+
+pub struct HealthServerPlugin;
+
+impl Plugin for HealthServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HealthConfig>()
+        .add_event::<Damage>()
+        .add_systems(FixedUpdate,
+            respawn_system
+            .before(BEFORE_PHYSICS_SET)
+        )
+        .add_systems(FixedUpdate, (
+            fall_damage_system,
+            apply_damage_system
+        ).chain(
+        ).after(AFTER_PHYSICS_SET));
+    }
+}
+
+fn fall_damage_system(
+    mut landed: EventReader<CharacterLanded>,
+    mut damage: EventWriter<Damage>,
+    characters: Query<(), (With<Health>, Without<Dead>)>,
+    config: Res<HealthConfig>
+) {
+    for CharacterLanded { entity, speed } in landed.read() {
+        let amount = config.fall_damage(*speed);
+        if amount <= 0.0 || !characters.contains(*entity) {
+            continue;
+        }
+        damage.send(Damage {
+            target: *entity,
+            amount,
+            source: DamageSource::Fall
+        });
+    }
+}
+
+fn apply_damage_system(
+    mut commands: Commands,
+    mut damage: EventReader<Damage>,
+    mut characters: Query<(&NetworkId, &RoomId, &mut Health, &mut LinearVelocity), Without<Dead>>,
+    mut died: EventWriter<ToClients<CharacterDied>>,
+    rooms: Res<Rooms>,
+    config: Res<HealthConfig>
+) {
+    for Damage { target, amount, source } in damage.read() {
+        if !amount.is_finite() || *amount <= 0.0 {
+            continue;
+        }
+        // also skips damage after death in the same tick
+        let Ok((net_id, room, mut health, mut vel)) = characters.get_mut(*target) else {
+            continue;
+        };
+        if !health.is_alive() {
+            continue;
+        }
+        health.current = (health.current - amount).max(0.0);
+        if health.is_alive() {
+            continue;
+        }
+
+        // no ragdoll, the body keeps falling but stops walking
+        vel.x = 0.0;
+        vel.z = 0.0;
+        commands.entity(*target)
+        .insert((
+            Dead,
+            RespawnTimer(Timer::new(
+                Duration::from_secs_f32(config.respawn_delay.max(0.0)),
+                TimerMode::Once
+            ))
+        ));
+
+        // clients in other rooms do not see the character
+        let client_id = net_id.client_id();
+        for member in rooms.members(*room) {
+            died.send(ToClients {
+                mode: SendMode::Direct(member),
+                event: CharacterDied {
+                    client_id,
+                    source: *source
+                }
+            });
+        }
+        info!("client: {client_id:?} died by: {source:?}");
+    }
+}
+
+fn respawn_system(
+    mut commands: Commands,
+    mut dead: Query<(Entity, &mut RespawnTimer)>,
+    mut characters: Query<(
+        Entity,
+        &NetworkId,
+        &RoomId,
+        &mut Health,
+        &mut Position,
+        &mut LinearVelocity,
+        &mut FallTracker
+    )>,
+    rooms: Res<Rooms>,
+    mut selector: SpawnSelector,
+    mut respawned: EventWriter<ToClients<CharacterRespawned>>,
+    time: Res<Time>
+) {
+    let ready = dead.iter_mut()
+    .filter_map(|(e, mut timer)| timer.0.tick(time.delta())
+        .finished()
+        .then_some(e)
+    )
+    .collect::<Vec<_>>();
+    if ready.is_empty() {
+        return;
+    }
+
+    let snapshot = characters.iter()
+    .map(|(e, _, room, _, pos, ..)| (e, *room, pos.0))
+    .collect::<Vec<_>>();
+    for e in ready {
+        let Some(room) = snapshot.iter()
+        .find(|(other, ..)| *other == e)
+        .map(|(_, room, _)| *room) else {
+            continue;
+        };
+        let Some(position) = selector.select_respawn(&rooms, e, room, &snapshot) else {
+            continue;
+        };
+        let Ok((_, net_id, _, mut health, mut pos, mut vel, mut fall)) = characters.get_mut(e) else {
+            continue;
+        };
+        health.current = health.max;
+        pos.0 = position;
+        vel.0 = Vec3::ZERO;
+        fall.reset();
+        commands.entity(e)
+        .remove::<(Dead, RespawnTimer)>();
+
+        let client_id = net_id.client_id();
+        for member in rooms.members(room) {
+            respawned.send(ToClients {
+                mode: SendMode::Direct(member),
+                event: CharacterRespawned { client_id, position }
+            });
+        }
+        info!("client: {client_id:?} respawned at: {position}");
+    }
+}
+
+pub struct HealthClientPlugin;
+
+impl Plugin for HealthClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate,
+            receive_death_system
+            .after(ClientSet::Receive)
+        );
+    }
+}
+
+fn receive_death_system(mut died: EventReader<CharacterDied>) {
+    for CharacterDied { client_id, source } in died.read() {
+        info!("player: {client_id:?} died by: {source:?}");
+    }
+}
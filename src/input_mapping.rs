@@ -0,0 +1,383 @@
+// maps keyboard, mouse and gamepad to actions
+// bindings are loaded from ron file and can be rebound at runtime
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf}
+};
+use bevy::{
+    prelude::*,
+    asset::ron,
+    ecs::system::SystemParam
+};
+use serde::{Serialize, Deserialize};
+
+pub const DEFAULT_STICK_DEADZONE: f32 = 0.15;
+// stick fully tilted for one fixed tick equals this much mouse motion
+pub const DEFAULT_STICK_LOOK_SENSITIVITY: f32 = 10.0;
+pub const DEFAULT_MOUSE_SENSITIVITY: f32 = 1.0;
+
+const CANCEL_REBIND: KeyCode = KeyCode::Escape;
+// pressing one starts rebinding its action
+const REBIND_KEYS: [(KeyCode, InputAction); 9] = [
+    (KeyCode::F1, InputAction::Forward),
+    (KeyCode::F2, InputAction::Back),
+    (KeyCode::F3, InputAction::Left),
+    (KeyCode::F4, InputAction::Right),
+    (KeyCode::F5, InputAction::Jump),
+    (KeyCode::F6, InputAction::Descend),
+    (KeyCode::F7, InputAction::Interact),
+    (KeyCode::F8, InputAction::Fire),
+    (KeyCode::F9, InputAction::FireHitscan)
+];
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum InputAction {
+    Forward,
+    Back,
+    Left,
+    Right,
+    Jump,
+    // sinks while swimming
+    Descend,
+    Interact,
+    // travelling projectile
+    Fire,
+    FireHitscan
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ButtonBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StickBinding {
+    LeftStick,
+    RightStick
+}
+
+impl StickBinding {
+    #[inline]
+    fn axes(&self) -> (GamepadAxisType, GamepadAxisType) {
+        match self {
+            StickBinding::LeftStick => (
+                GamepadAxisType::LeftStickX,
+                GamepadAxisType::LeftStickY
+            ),
+            StickBinding::RightStick => (
+                GamepadAxisType::RightStickX,
+                GamepadAxisType::RightStickY
+            )
+        }
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct InputMap {
+    pub buttons: BTreeMap<InputAction, Vec<ButtonBinding>>,
+    pub move_stick: Option<StickBinding>,
+    pub look_stick: Option<StickBinding>,
+    pub stick_deadzone: f32,
+    pub stick_look_sensitivity: f32,
+    pub mouse_sensitivity: f32
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self {
+            buttons: BTreeMap::from([
+                (InputAction::Forward, vec![
+                    ButtonBinding::Key(KeyCode::KeyW),
+                    ButtonBinding::Gamepad(GamepadButtonType::DPadUp)
+                ]),
+                (InputAction::Back, vec![
+                    ButtonBinding::Key(KeyCode::KeyS),
+                    ButtonBinding::Gamepad(GamepadButtonType::DPadDown)
+                ]),
+                (InputAction::Left, vec![
+                    ButtonBinding::Key(KeyCode::KeyA),
+                    ButtonBinding::Gamepad(GamepadButtonType::DPadLeft)
+                ]),
+                (InputAction::Right, vec![
+                    ButtonBinding::Key(KeyCode::KeyD),
+                    ButtonBinding::Gamepad(GamepadButtonType::DPadRight)
+                ]),
+                (InputAction::Jump, vec![
+                    ButtonBinding::Key(KeyCode::Space),
+                    ButtonBinding::Gamepad(GamepadButtonType::South)
+                ]),
+                (InputAction::Descend, vec![
+                    ButtonBinding::Key(KeyCode::KeyC),
+                    ButtonBinding::Gamepad(GamepadButtonType::East)
+                ]),
+                (InputAction::Interact, vec![
+                    ButtonBinding::Key(KeyCode::KeyE),
+                    ButtonBinding::Gamepad(GamepadButtonType::West)
+                ]),
+                (InputAction::Fire, vec![
+                    ButtonBinding::Mouse(MouseButton::Left),
+                    ButtonBinding::Gamepad(GamepadButtonType::RightTrigger2)
+                ]),
+                (InputAction::FireHitscan, vec![
+                    ButtonBinding::Mouse(MouseButton::Right),
+                    ButtonBinding::Gamepad(GamepadButtonType::LeftTrigger2)
+                ])
+            ]),
+            move_stick: Some(StickBinding::LeftStick),
+            look_stick: Some(StickBinding::RightStick),
+            stick_deadzone: DEFAULT_STICK_DEADZONE,
+            stick_look_sensitivity: DEFAULT_STICK_LOOK_SENSITIVITY,
+            mouse_sensitivity: DEFAULT_MOUSE_SENSITIVITY
+        }
+    }
+}
+
+impl InputMap {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let s = fs::read_to_string(path)?;
+        Ok(ron::from_str(&s)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let s = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, s)?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn bindings(&self, action: InputAction) -> &[ButtonBinding] {
+        self.buttons.get(&action)
+        .map_or(&[], Vec::as_slice)
+    }
+
+    // one binding can only trigger one action
+    pub fn bind(&mut self, action: InputAction, binding: ButtonBinding) {
+        for bindings in self.buttons.values_mut() {
+            bindings.retain(|b| *b != binding);
+        }
+        self.buttons.entry(action)
+        .or_default()
+        .push(binding);
+    }
+
+    #[inline]
+    pub fn unbind(&mut self, action: InputAction, binding: ButtonBinding) {
+        if let Some(bindings) = self.buttons.get_mut(&action) {
+            bindings.retain(|b| *b != binding);
+        }
+    }
+
+    // radial deadzone, rescaled so magnitude starts from zero at the edge
+    #[inline]
+    pub fn apply_deadzone(&self, stick: Vec2) -> Vec2 {
+        let len = stick.length();
+        if len <= self.stick_deadzone {
+            return Vec2::ZERO;
+        }
+        let scaled = ((len - self.stick_deadzone) / (1.0 - self.stick_deadzone))
+        .min(1.0);
+        stick / len * scaled
+    }
+}
+
+// where rebinds are saved
+#[derive(Resource)]
+pub struct InputMapPath(pub PathBuf);
+
+// next pressed button replaces bindings of the action
+#[derive(Event)]
+pub struct StartRebind(pub InputAction);
+
+#[derive(Resource, Default)]
+struct PendingRebind(Option<InputAction>);
+
+#[derive(SystemParam)]
+pub struct ActionInput<'w> {
+    map: Res<'w, InputMap>,
+    keyboard: Res<'w, ButtonInput<KeyCode>>,
+    mouse_buttons: Res<'w, ButtonInput<MouseButton>>,
+    gamepad_buttons: Res<'w, ButtonInput<GamepadButton>>,
+    gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+    gamepads: Res<'w, Gamepads>
+}
+
+impl<'w> ActionInput<'w> {
+    #[inline]
+    pub fn map(&self) -> &InputMap {
+        &self.map
+    }
+
+    pub fn pressed(&self, action: InputAction) -> bool {
+        self.map.bindings(action).iter()
+        .any(|b| match b {
+            ButtonBinding::Key(key) => self.keyboard.pressed(*key),
+            ButtonBinding::Mouse(button) => self.mouse_buttons.pressed(*button),
+            ButtonBinding::Gamepad(button) => self.gamepads.iter()
+                .any(|g| self.gamepad_buttons.pressed(GamepadButton::new(g, *button)))
+        })
+    }
+
+    pub fn just_pressed(&self, action: InputAction) -> bool {
+        self.map.bindings(action).iter()
+        .any(|b| match b {
+            ButtonBinding::Key(key) => self.keyboard.just_pressed(*key),
+            ButtonBinding::Mouse(button) => self.mouse_buttons.just_pressed(*button),
+            ButtonBinding::Gamepad(button) => self.gamepads.iter()
+                .any(|g| self.gamepad_buttons.just_pressed(GamepadButton::new(g, *button)))
+        })
+    }
+
+    // first gamepad with a non zero value after deadzone
+    pub fn stick(&self, stick: StickBinding) -> Vec2 {
+        let (x, y) = stick.axes();
+        self.gamepads.iter()
+        .map(|g| self.map.apply_deadzone(Vec2::new(
+            self.gamepad_axes.get(GamepadAxis::new(g, x)).unwrap_or_default(),
+            self.gamepad_axes.get(GamepadAxis::new(g, y)).unwrap_or_default()
+        )))
+        .find(|v| *v != Vec2::ZERO)
+        .unwrap_or_default()
+    }
+
+    // x: right, y: forward, length <= 1
+    pub fn linear(&self) -> Vec2 {
+        let mut linear = Vec2::ZERO;
+        if self.pressed(InputAction::Forward) {
+            linear.y += 1.0;
+        }
+        if self.pressed(InputAction::Back) {
+            linear.y -= 1.0;
+        }
+        if self.pressed(InputAction::Right) {
+            linear.x += 1.0;
+        }
+        if self.pressed(InputAction::Left) {
+            linear.x -= 1.0;
+        }
+        if let Some(stick) = self.map.move_stick {
+            linear += self.stick(stick);
+        }
+        linear.clamp_length_max(1.0)
+    }
+
+    // held jump rises, held descend sinks
+    pub fn vertical(&self) -> f32 {
+        let mut vertical = 0.0;
+        if self.pressed(InputAction::Jump) {
+            vertical += 1.0;
+        }
+        if self.pressed(InputAction::Descend) {
+            vertical -= 1.0;
+        }
+        vertical
+    }
+
+    // in mouse motion units, y is down like mouse
+    pub fn look_stick(&self) -> Vec2 {
+        let Some(stick) = self.map.look_stick else {
+            return Vec2::ZERO;
+        };
+        let v = self.stick(stick);
+        Vec2::new(v.x, -v.y) * self.map.stick_look_sensitivity
+    }
+}
+
+pub struct InputMappingPlugin;
+
+impl Plugin for InputMappingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputMap>()
+        .init_resource::<PendingRebind>()
+        .add_event::<StartRebind>()
+        .add_systems(PreUpdate, (
+            rebind_keys_system,
+            // started after capture, so the key that started it is not captured
+            capture_rebind_system,
+            start_rebind_system
+        ).chain(
+        ).after(bevy::input::InputSystem));
+    }
+}
+
+// while a rebind is pending these keys are captured as bindings
+fn rebind_keys_system(
+    mut events: EventWriter<StartRebind>,
+    pending: Res<PendingRebind>,
+    keyboard: Res<ButtonInput<KeyCode>>
+) {
+    if pending.0.is_some() {
+        return;
+    }
+    if let Some((_, action)) = REBIND_KEYS.iter()
+    .find(|(key, _)| keyboard.just_pressed(*key)) {
+        events.send(StartRebind(*action));
+    }
+}
+
+fn start_rebind_system(
+    mut events: EventReader<StartRebind>,
+    mut pending: ResMut<PendingRebind>
+) {
+    if let Some(StartRebind(action)) = events.read().last() {
+        info!("press a button for {action:?}, {CANCEL_REBIND:?} to cancel");
+        pending.0 = Some(*action);
+    }
+}
+
+fn capture_rebind_system(
+    mut pending: ResMut<PendingRebind>,
+    mut map: ResMut<InputMap>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    gamepad_buttons: Res<ButtonInput<GamepadButton>>,
+    path: Option<Res<InputMapPath>>
+) {
+    let Some(action) = pending.0 else {
+        return;
+    };
+
+    if keyboard.just_pressed(CANCEL_REBIND) {
+        info!("rebind of {action:?} canceled");
+        pending.0 = None;
+        return;
+    }
+
+    let Some(binding) = keyboard.get_just_pressed()
+    .next()
+    .map(|k| ButtonBinding::Key(*k))
+    .or_else(|| mouse_buttons.get_just_pressed()
+        .next()
+        .map(|b| ButtonBinding::Mouse(*b))
+    )
+    .or_else(|| gamepad_buttons.get_just_pressed()
+        .next()
+        .map(|b| ButtonBinding::Gamepad(b.button_type))
+    ) else {
+        return;
+    };
+
+    // replace same kind of binding, keep others
+    // e.g. rebinding a key keeps the gamepad button
+    let same_kind = |b: &ButtonBinding| std::mem::discriminant(b) == std::mem::discriminant(&binding);
+    let old = map.bindings(action).iter()
+    .copied()
+    .filter(same_kind)
+    .collect::<Vec<_>>();
+    for b in old {
+        map.unbind(action, b);
+    }
+    map.bind(action, binding);
+    pending.0 = None;
+    info!("{action:?} bound to {binding:?}");
+
+    if let Some(path) = path {
+        if let Err(e) = map.save(&path.0) {
+            error!("failed to save input map: {e}");
+        }
+    }
+}
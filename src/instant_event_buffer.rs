@@ -1,26 +1,323 @@
-use std::vec::Drain;
-use bevy::prelude::*;
+// per entity event queue, written and read within the same few fixed ticks
+// events are stamped with SimulationTick, stale ones expire instead of being
+// applied late, and the queue is bounded if its consumer stops running
+
+use std::collections::{
+    VecDeque,
+    vec_deque::Drain
+};
+use bevy::{
+    prelude::*,
+    ecs::system::SystemParam
+};
+use crate::SimulationTick;
+
+pub const INSTANT_EVENT_BUFFER_CAPACITY: usize = 32;
+// in fixed ticks, events are normally read in the tick they were sent
+pub const INSTANT_EVENT_MAX_AGE: u32 = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum OverflowPolicy {
+    // keeps the latest events
+    #[default]
+    DropOldest,
+    // keeps the earliest events, rejects new ones
+    DropNewest
+}
 
 #[derive(Component)]
 pub struct InstantEventBuffer<E: Event> {
-    buff: Vec<E>
+    buff: VecDeque<(u32, E)>,
+    capacity: usize,
+    overflow: OverflowPolicy,
+    max_age: Option<u32>
+}
+
+impl<E: Event> Default for InstantEventBuffer<E> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<E: Event> InstantEventBuffer<E> {
     #[inline]
     pub fn new() -> Self {
-        Self { 
-            buff: Vec::<E>::new() 
+        Self {
+            buff: VecDeque::new(),
+            capacity: INSTANT_EVENT_BUFFER_CAPACITY,
+            overflow: OverflowPolicy::default(),
+            max_age: Some(INSTANT_EVENT_MAX_AGE)
         }
     }
 
+    // capacity is at least 1
+    #[inline]
+    pub fn with_capacity(mut self, capacity: usize, overflow: OverflowPolicy) -> Self {
+        self.capacity = capacity.max(1);
+        self.overflow = overflow;
+        self
+    }
+
+    // None keeps events until they are read
     #[inline]
-    pub fn send(&mut self, e: E) {
-        self.buff.push(e);
+    pub fn with_max_age(mut self, max_age: Option<u32>) -> Self {
+        self.max_age = max_age;
+        self
     }
 
+    // false if the event was rejected by DropNewest
+    pub fn send(&mut self, tick: u32, e: E) -> bool {
+        if self.buff.len() >= self.capacity {
+            match self.overflow {
+                OverflowPolicy::DropOldest => {
+                    self.buff.pop_front();
+                }
+                OverflowPolicy::DropNewest => return false
+            }
+        }
+        self.buff.push_back((tick, e));
+        true
+    }
+
+    // drops expired events, then drains the rest in send order
     #[inline]
-    pub fn read(&mut self) -> Drain<'_, E> {
+    pub fn read(&mut self, tick: u32) -> Drain<'_, (u32, E)> {
+        self.expire(tick);
         self.buff.drain(..)
     }
+
+    // returns the number of dropped events
+    pub fn expire(&mut self, tick: u32) -> usize {
+        let Some(max_age) = self.max_age else {
+            return 0;
+        };
+        let len = self.buff.len();
+        // wrapping, same as SimulationTick
+        self.buff.retain(|(stamp, _)| tick.wrapping_sub(*stamp) <= max_age);
+        len - self.buff.len()
+    }
+
+    // peek and iter do not expire, call expire first for a current view
+    #[inline]
+    pub fn peek(&self) -> Option<&E> {
+        self.buff.front().map(|(_, e)| e)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.buff.iter().map(|(_, e)| e)
+    }
+
+    #[inline]
+    pub fn iter_stamped(&self) -> impl Iterator<Item = (u32, &E)> {
+        self.buff.iter().map(|(tick, e)| (*tick, e))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.buff.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buff.is_empty()
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.buff.clear();
+    }
+}
+
+// buffers of every entity, stamped and expired with the current SimulationTick
+#[derive(SystemParam)]
+pub struct InstantEvents<'w, 's, E: Event> {
+    tick: Res<'w, SimulationTick>,
+    buffers: Query<'w, 's, (Entity, &'static mut InstantEventBuffer<E>)>
+}
+
+impl<'w, 's, E: Event> InstantEvents<'w, 's, E> {
+    #[inline]
+    pub fn tick(&self) -> u32 {
+        self.tick.get()
+    }
+
+    // false if the entity has no buffer or the event was rejected
+    pub fn send(&mut self, entity: Entity, e: E) -> bool {
+        let tick = self.tick.get();
+        match self.buffers.get_mut(entity) {
+            Ok((_, mut buffer)) => buffer.send(tick, e),
+            Err(_) => false
+        }
+    }
+
+    // drains the entity buffer, empty if the entity has none
+    pub fn read(&mut self, entity: Entity) -> Vec<E> {
+        let tick = self.tick.get();
+        match self.buffers.get_mut(entity) {
+            Ok((_, mut buffer)) => buffer.read(tick)
+            .map(|(_, e)| e)
+            .collect(),
+            Err(_) => Vec::new()
+        }
+    }
+
+    // without draining, expired events are dropped first
+    pub fn get_mut(&mut self, entity: Entity) -> Option<Mut<'_, InstantEventBuffer<E>>> {
+        let tick = self.tick.get();
+        let (_, mut buffer) = self.buffers.get_mut(entity).ok()?;
+        buffer.expire(tick);
+        Some(buffer)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, Mut<'_, InstantEventBuffer<E>>)> {
+        let tick = self.tick.get();
+        self.buffers.iter_mut()
+        .map(move |(e, mut buffer)| {
+            buffer.expire(tick);
+            (e, buffer)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Event, Clone, Copy, PartialEq, Debug)]
+    struct Ping(u32);
+
+    fn drain(buffer: &mut InstantEventBuffer<Ping>, tick: u32) -> Vec<u32> {
+        buffer.read(tick)
+        .map(|(_, e)| e.0)
+        .collect()
+    }
+
+    #[test]
+    fn read_drains_in_send_order() {
+        let mut buffer = InstantEventBuffer::new();
+        for i in 0..3 {
+            assert!(buffer.send(0, Ping(i)));
+        }
+        assert_eq!(drain(&mut buffer, 0), vec![0, 1, 2]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn drop_oldest_keeps_latest() {
+        let mut buffer = InstantEventBuffer::new()
+        .with_capacity(2, OverflowPolicy::DropOldest);
+        for i in 0..4 {
+            assert!(buffer.send(0, Ping(i)));
+        }
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(drain(&mut buffer, 0), vec![2, 3]);
+    }
+
+    #[test]
+    fn drop_newest_rejects_on_overflow() {
+        let mut buffer = InstantEventBuffer::new()
+        .with_capacity(2, OverflowPolicy::DropNewest);
+        assert!(buffer.send(0, Ping(0)));
+        assert!(buffer.send(0, Ping(1)));
+        assert!(!buffer.send(0, Ping(2)));
+        assert_eq!(drain(&mut buffer, 0), vec![0, 1]);
+    }
+
+    #[test]
+    fn unread_events_stay_bounded() {
+        let mut buffer = InstantEventBuffer::new();
+        for tick in 0..1000 {
+            buffer.send(tick, Ping(tick));
+        }
+        assert_eq!(buffer.len(), INSTANT_EVENT_BUFFER_CAPACITY);
+    }
+
+    #[test]
+    fn stale_events_expire() {
+        let mut buffer = InstantEventBuffer::new()
+        .with_max_age(Some(2));
+        buffer.send(0, Ping(0));
+        buffer.send(1, Ping(1));
+        buffer.send(3, Ping(3));
+        assert_eq!(drain(&mut buffer, 3), vec![1, 3]);
+    }
+
+    #[test]
+    fn expire_counts_dropped_and_handles_wrapping() {
+        let mut buffer = InstantEventBuffer::new()
+        .with_max_age(Some(2));
+        buffer.send(u32::MAX - 1, Ping(0));
+        buffer.send(u32::MAX, Ping(1));
+        buffer.send(0, Ping(2));
+        assert_eq!(buffer.expire(1), 1);
+        assert_eq!(drain(&mut buffer, 1), vec![1, 2]);
+    }
+
+    #[test]
+    fn without_max_age_nothing_expires() {
+        let mut buffer = InstantEventBuffer::new()
+        .with_max_age(None);
+        buffer.send(0, Ping(0));
+        assert_eq!(drain(&mut buffer, 10_000), vec![0]);
+    }
+
+    #[test]
+    fn peek_and_iter_do_not_drain() {
+        let mut buffer = InstantEventBuffer::new();
+        buffer.send(4, Ping(0));
+        buffer.send(5, Ping(1));
+        assert_eq!(buffer.peek(), Some(&Ping(0)));
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), vec![Ping(0), Ping(1)]);
+        assert_eq!(
+            buffer.iter_stamped().map(|(t, e)| (t, *e)).collect::<Vec<_>>(),
+            vec![(4, Ping(0)), (5, Ping(1))]
+        );
+        assert_eq!(buffer.len(), 2);
+    }
+
+    #[derive(Resource)]
+    struct Target(Entity);
+
+    #[derive(Resource, Default)]
+    struct Received(Vec<u32>);
+
+    fn send_system(mut events: InstantEvents<Ping>, target: Res<Target>) {
+        let tick = events.tick();
+        events.send(target.0, Ping(tick));
+    }
+
+    fn read_system(mut events: InstantEvents<Ping>, mut received: ResMut<Received>) {
+        let entities = events.iter_mut()
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+        for e in entities {
+            received.0.extend(events.read(e).into_iter().map(|p| p.0));
+        }
+    }
+
+    #[test]
+    fn system_param_sends_and_reads_across_entities() {
+        let mut app = App::new();
+        app.init_resource::<SimulationTick>()
+        .init_resource::<Received>();
+        let with_buffer = app.world.spawn(InstantEventBuffer::<Ping>::new()).id();
+        let without_buffer = app.world.spawn_empty().id();
+        app.insert_resource(Target(with_buffer))
+        .add_systems(Update, (send_system, read_system).chain());
+
+        app.update();
+        app.update();
+        assert_eq!(app.world.resource::<Received>().0, vec![0, 0]);
+
+        app.insert_resource(Target(without_buffer));
+        app.update();
+        assert_eq!(app.world.resource::<Received>().0.len(), 2);
+    }
 }
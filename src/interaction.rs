@@ -0,0 +1,264 @@
+// world objects players use, like switches
+// client only asks to interact, server picks the target by a ray from
+// the character eye along its yaw, so range and line of sight are not trusted
+// validated interactions are sent as Interacted, results replicate in InteractionState
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::{
+    *,
+    input_mapping::{ActionInput, InputAction},
+    health::Dead
+};
+
+pub const INTERACT_RANGE: f32 = 2.5;
+// upper bound of any Interactable range
+pub const INTERACT_MAX_RANGE: f32 = 5.0;
+// sensors in front of the target, like water, do not block
+const INTERACT_MAX_HITS: u32 = 8;
+
+pub const INTERACTABLE_COLOR: Color = Color::rgb(0.8, 0.2, 0.2);
+pub const INTERACTABLE_ACTIVE_COLOR: Color = Color::rgb(0.2, 0.8, 0.2);
+
+// replicated, so clients can show prompts
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Interactable {
+    // from the character eye, clamped to INTERACT_MAX_RANGE
+    pub range: f32
+}
+
+impl Default for Interactable {
+    fn default() -> Self {
+        Self {
+            range: INTERACT_RANGE
+        }
+    }
+}
+
+// replicated result, updated for every Interacted
+#[derive(Component, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+pub struct InteractionState {
+    pub active: bool,
+    pub count: u32,
+    pub last_user: Option<ClientId>
+}
+
+// replicated, clients build the visual from it like props
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct NetworkInteractable {
+    pub translation: Vec3,
+    pub size: Vec3
+}
+
+// client to server, target is chosen by server
+#[derive(Event, Serialize, Deserialize, Default, Clone, Copy, Debug)]
+pub struct InteractRequest;
+
+// server side, read by game logic of the target
+#[derive(Event, Clone, Copy, Debug)]
+pub struct Interacted {
+    pub target: Entity,
+    pub interactor: Entity,
+    pub client_id: ClientId
+}
+
+// server side, extra is added like level entities
+pub fn server_spawn_interactable(
+    commands: &mut Commands,
+    position: Vec3,
+    size: Vec3,
+    extra: impl Bundle
+) {
+    commands.spawn((
+        Replicated,
+        Interactable::default(),
+        InteractionState::default(),
+        NetworkInteractable {
+            translation: position,
+            size
+        },
+        TransformBundle::from_transform(
+            Transform::from_translation(position)
+        ),
+        RigidBody::Static,
+        Collider::cuboid(size.x, size.y, size.z),
+        extra
+    ));
+}
+
+// nearest hit decides, sensors are looked through,
+// a collider child counts as its interactable parent
+pub fn find_interaction_target(
+    spatial_query: &SpatialQuery,
+    origin: Vec3,
+    direction: Direction3d,
+    filter: SpatialQueryFilter,
+    interactables: &Query<&Interactable>,
+    parents: &Query<&Parent>,
+    sensors: &Query<(), With<Sensor>>
+) -> Option<Entity> {
+    let mut hits = spatial_query.ray_hits(
+        origin,
+        direction,
+        INTERACT_MAX_RANGE,
+        INTERACT_MAX_HITS,
+        true,
+        filter
+    );
+    hits.sort_by(|a, b| a.time_of_impact.total_cmp(&b.time_of_impact));
+
+    for hit in hits {
+        let target = std::iter::once(hit.entity)
+        .chain(parents.get(hit.entity).map(|p| p.get()))
+        .find(|e| interactables.contains(*e));
+        if let Some(target) = target {
+            let range = interactables.get(target)
+            .map_or(0.0, |i| i.range.min(INTERACT_MAX_RANGE));
+            return (hit.time_of_impact <= range).then_some(target);
+        }
+        if !sensors.contains(hit.entity) {
+            return None;
+        }
+    }
+    None
+}
+
+pub struct InteractionServerPlugin;
+
+impl Plugin for InteractionServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Interacted>()
+        // recorded with actions, so replay interacts in the same tick
+        .add_systems(FixedUpdate, (
+            handle_interact_request,
+            apply_interaction_system
+        ).chain(
+        ).before(BEFORE_PHYSICS_SET));
+    }
+}
+
+fn handle_interact_request(
+    mut requests: EventReader<FromClient<InteractRequest>>,
+    mut interacted: EventWriter<Interacted>,
+    characters: Query<(Entity, &NetworkId, &Transform, &CollisionLayers), Without<Dead>>,
+    interactables: Query<&Interactable>,
+    parents: Query<&Parent>,
+    sensors: Query<(), With<Sensor>>,
+    spatial_query: SpatialQuery
+) {
+    for FromClient { client_id, .. } in requests.read() {
+        let Some((e, _, transform, layers)) = characters.iter()
+        .find(|(_, net_id, ..)| net_id.client_id() == *client_id) else {
+            continue;
+        };
+
+        let origin = transform.translation + Vec3::Y * CHARACTER_EYE_HEIGHT;
+        let Ok(direction) = Direction3d::new(facing(transform.rotation)) else {
+            continue;
+        };
+        // own room only, never the character itself
+        let filter = SpatialQueryFilter::from_mask(layers.filters)
+        .with_excluded_entities([e]);
+
+        match find_interaction_target(
+            &spatial_query,
+            origin,
+            direction,
+            filter,
+            &interactables,
+            &parents,
+            &sensors
+        ) {
+            Some(target) => {
+                interacted.send(Interacted {
+                    target,
+                    interactor: e,
+                    client_id: *client_id
+                });
+            }
+            None => debug!("client: {client_id:?} has nothing to interact with")
+        }
+    }
+}
+
+// toggles, game logic may read Interacted for anything else
+fn apply_interaction_system(
+    mut interacted: EventReader<Interacted>,
+    mut states: Query<&mut InteractionState>
+) {
+    for Interacted { target, client_id, .. } in interacted.read() {
+        let Ok(mut state) = states.get_mut(*target) else {
+            continue;
+        };
+        state.active = !state.active;
+        state.count = state.count.wrapping_add(1);
+        state.last_user = Some(*client_id);
+        info!("client: {client_id:?} interacted with: {target:?}, active: {}", state.active);
+    }
+}
+
+pub struct InteractionClientPlugin;
+
+impl Plugin for InteractionClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, (
+            handle_interactable_spawn,
+            update_interactable_color
+        ).chain(
+        ).after(ClientSet::Receive))
+        .add_systems(Update, send_interact_request);
+    }
+}
+
+#[inline]
+fn interactable_color(state: &InteractionState) -> Color {
+    if state.active {
+        INTERACTABLE_ACTIVE_COLOR
+    } else {
+        INTERACTABLE_COLOR
+    }
+}
+
+fn handle_interactable_spawn(
+    mut commands: Commands,
+    query: Query<(Entity, &NetworkInteractable, &InteractionState), Added<NetworkInteractable>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    for (e, net_interactable, state) in query.iter() {
+        let size = net_interactable.size;
+        commands.entity(e)
+        .insert((
+            PbrBundle {
+                mesh: meshes.add(Mesh::from(Cuboid::from_size(size))),
+                material: materials.add(interactable_color(state)),
+                transform: Transform::from_translation(net_interactable.translation),
+                ..default()
+            },
+            RigidBody::Static,
+            Collider::cuboid(size.x, size.y, size.z)
+        ));
+    }
+}
+
+fn update_interactable_color(
+    query: Query<(&InteractionState, &Handle<StandardMaterial>), Changed<InteractionState>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    for (state, handle) in query.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color = interactable_color(state);
+        }
+    }
+}
+
+fn send_interact_request(
+    input: ActionInput,
+    mut requests: EventWriter<InteractRequest>
+) {
+    if input.just_pressed(InputAction::Interact) {
+        requests.send(InteractRequest);
+    }
+}
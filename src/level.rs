@@ -1,5 +1,12 @@
 use bevy::prelude::*;
 use bevy_xpbd_3d::prelude::*;
+use crate::{
+    CHARACTER_SPAWN_POSITION,
+    spawn::{SpawnPoint, KillBounds},
+    props::{Prop, PROP_CRATE_SIZE, PROP_BALL_RADIUS, server_spawn_prop},
+    character_controller::{LiquidVolume, Climbable},
+    interaction::server_spawn_interactable
+};
 
 pub const FLOOR_SIZE: Vec3 = Vec3::new(100.0, 1.0, 100.0);
 pub const FLOOR_COLOR: Color = Color::rgb(0.5, 0.5, 0.5);
@@ -15,6 +22,52 @@ pub const BOX_POSITION_2: Vec3 = Vec3::new(-5.0, 2.5, 5.0);
 pub const BOX_POSITION_3: Vec3 = Vec3::new(5.0, 2.5, -5.0);
 pub const BOX_POSITION_4: Vec3 = Vec3::new(-5.0, 2.5, -5.0);
 
+const SPAWN_HEIGHT: f32 = CHARACTER_SPAWN_POSITION.y;
+pub const SPAWN_POINT_POSITIONS: [Vec3; 9] = [
+    Vec3::new(0.0, SPAWN_HEIGHT, 0.0),
+    Vec3::new(12.0, SPAWN_HEIGHT, 0.0),
+    Vec3::new(-12.0, SPAWN_HEIGHT, 0.0),
+    Vec3::new(0.0, SPAWN_HEIGHT, 12.0),
+    Vec3::new(0.0, SPAWN_HEIGHT, -12.0),
+    Vec3::new(15.0, SPAWN_HEIGHT, 15.0),
+    Vec3::new(-15.0, SPAWN_HEIGHT, 15.0),
+    Vec3::new(15.0, SPAWN_HEIGHT, -15.0),
+    Vec3::new(-15.0, SPAWN_HEIGHT, -15.0)
+];
+// a little wider than the floor, so walking off the edge is visible before respawn
+pub const KILL_BOUNDS_MIN: Vec3 = Vec3::new(-60.0, -30.0, -60.0);
+pub const KILL_BOUNDS_MAX: Vec3 = Vec3::new(60.0, 200.0, 60.0);
+
+pub const PROP_CRATE_POSITIONS: [Vec3; 2] = [
+    Vec3::new(6.0, PROP_CRATE_SIZE * 0.5, 20.0),
+    Vec3::new(-6.0, PROP_CRATE_SIZE * 0.5, 20.0)
+];
+pub const PROP_BALL_POSITIONS: [Vec3; 2] = [
+    Vec3::new(6.0, PROP_BALL_RADIUS, -20.0),
+    Vec3::new(-6.0, PROP_BALL_RADIUS, -20.0)
+];
+
+// pool standing on the floor, deep enough to swim
+pub const WATER_SIZE: Vec3 = Vec3::new(12.0, 3.0, 12.0);
+pub const WATER_POSITION: Vec3 = Vec3::new(-25.0, WATER_SIZE.y * 0.5, 0.0);
+pub const WATER_COLOR: Color = Color::rgba(0.1, 0.3, 0.8, 0.4);
+pub const WATER_DENSITY: f32 = 1.2;
+pub const WATER_DRAG: f32 = 2.0;
+
+// on the +x side of the box at BOX_POSITION_1, up to above its top
+pub const LADDER_SIZE: Vec3 = Vec3::new(0.8, BOX_SIZE.y + 1.5, 1.2);
+pub const LADDER_POSITION: Vec3 = Vec3::new(
+    BOX_POSITION_1.x + BOX_SIZE.x * 0.5 + LADDER_SIZE.x * 0.5,
+    LADDER_SIZE.y * 0.5,
+    BOX_POSITION_1.z
+);
+pub const LADDER_COLOR: Color = Color::rgb(0.5, 0.3, 0.1);
+// rendered as a thin board against the box
+const LADDER_BOARD_THICKNESS: f32 = 0.1;
+
+// post facing the spawn at the center, in reach at eye height
+pub const SWITCH_SIZE: Vec3 = Vec3::new(0.4, 1.8, 0.4);
+pub const SWITCH_POSITION: Vec3 = Vec3::new(0.0, SWITCH_SIZE.y * 0.5, -6.0);
 
 pub fn client_setup_floor(
     mut commands: Commands,
@@ -32,22 +85,85 @@ pub fn client_setup_floor(
     ));
 }
 
-pub fn server_setup_floor(mut commands: Commands) {
+pub fn floor_collider() -> impl Bundle {
+    (
+        Collider::cuboid(FLOOR_SIZE.x, FLOOR_SIZE.y, FLOOR_SIZE.z),
+        RigidBody::Static
+    )
+}
+
+pub fn water_volume() -> impl Bundle {
+    (
+        Collider::cuboid(WATER_SIZE.x, WATER_SIZE.y, WATER_SIZE.z),
+        RigidBody::Static,
+        Sensor,
+        LiquidVolume {
+            density: WATER_DENSITY,
+            drag: WATER_DRAG
+        }
+    )
+}
+
+pub fn client_setup_water(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
     commands.spawn((
-        TransformBundle::from_transform(
-            Transform::from_translation(FLOOR_POSITION)
-        ),
-        floor_collider()
+        PbrBundle{
+            mesh: meshes.add(Mesh::from(Cuboid::from_size(WATER_SIZE))),
+            material: materials.add(StandardMaterial {
+                base_color: WATER_COLOR,
+                alpha_mode: AlphaMode::Blend,
+                ..default()
+            }),
+            transform: Transform::from_translation(WATER_POSITION),
+            ..default()
+        },
+        water_volume()
     ));
 }
 
-pub fn floor_collider() -> impl Bundle {
+pub fn ladder_volume() -> impl Bundle {
     (
-        Collider::cuboid(FLOOR_SIZE.x, FLOOR_SIZE.y, FLOOR_SIZE.z),
-        RigidBody::Static
+        Collider::cuboid(LADDER_SIZE.x, LADDER_SIZE.y, LADDER_SIZE.z),
+        RigidBody::Static,
+        Sensor,
+        Climbable {
+            normal: Vec3::X
+        }
     )
 }
 
+pub fn client_setup_ladder(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    commands.spawn((
+        SpatialBundle::from_transform(
+            Transform::from_translation(LADDER_POSITION)
+        ),
+        ladder_volume()
+    ))
+    .with_children(|parent| {
+        parent.spawn(PbrBundle{
+            mesh: meshes.add(Mesh::from(Cuboid::new(
+                LADDER_BOARD_THICKNESS, 
+                LADDER_SIZE.y, 
+                LADDER_SIZE.z
+            ))),
+            material: materials.add(LADDER_COLOR),
+            transform: Transform::from_xyz(
+                (LADDER_BOARD_THICKNESS - LADDER_SIZE.x) * 0.5, 
+                0.0, 
+                0.0
+            ),
+            ..default()
+        });
+    });
+}
+
 pub fn setup_light(mut commands: Commands) {
     commands.spawn(DirectionalLightBundle{
         directional_light: DirectionalLight{
@@ -121,29 +237,75 @@ pub fn client_setup_box(
     ));
 }
 
-pub fn server_setup_box(mut commands: Commands) {
+// extra is added to every spawned entity
+pub fn server_spawn_level(commands: &mut Commands, extra: impl Bundle + Clone) {
     commands.spawn((
         TransformBundle::from_transform(
-            Transform::from_translation(BOX_POSITION_1)
+            Transform::from_translation(FLOOR_POSITION)
         ),
-        box_collider()
+        floor_collider(),
+        extra.clone()
     ));
+    for position in [
+        BOX_POSITION_1,
+        BOX_POSITION_2,
+        BOX_POSITION_3,
+        BOX_POSITION_4
+    ] {
+        commands.spawn((
+            TransformBundle::from_transform(
+                Transform::from_translation(position)
+            ),
+            box_collider(),
+            extra.clone()
+        ));
+    }
+
     commands.spawn((
         TransformBundle::from_transform(
-            Transform::from_translation(BOX_POSITION_2)
+            Transform::from_translation(WATER_POSITION)
         ),
-        box_collider()
+        water_volume(),
+        extra.clone()
     ));
+
     commands.spawn((
         TransformBundle::from_transform(
-            Transform::from_translation(BOX_POSITION_3)
+            Transform::from_translation(LADDER_POSITION)
         ),
-        box_collider()
+        ladder_volume(),
+        extra.clone()
     ));
+
+    for position in SPAWN_POINT_POSITIONS {
+        commands.spawn((
+            TransformBundle::from_transform(
+                Transform::from_translation(position)
+            ),
+            SpawnPoint,
+            extra.clone()
+        ));
+    }
     commands.spawn((
-        TransformBundle::from_transform(
-            Transform::from_translation(BOX_POSITION_4)
-        ),
-        box_collider()
+        KillBounds{
+            min: KILL_BOUNDS_MIN,
+            max: KILL_BOUNDS_MAX
+        },
+        extra
     ));
 }
+
+// replicated, extra is added to every prop
+pub fn server_spawn_props(commands: &mut Commands, extra: impl Bundle + Clone) {
+    for position in PROP_CRATE_POSITIONS {
+        server_spawn_prop(commands, Prop::Crate, position, extra.clone());
+    }
+    for position in PROP_BALL_POSITIONS {
+        server_spawn_prop(commands, Prop::Ball, position, extra.clone());
+    }
+}
+
+// replicated, extra is added to every interactable
+pub fn server_spawn_interactables(commands: &mut Commands, extra: impl Bundle + Clone) {
+    server_spawn_interactable(commands, SWITCH_POSITION, SWITCH_SIZE, extra);
+}
@@ -7,8 +7,31 @@ pub mod game_client;
 pub mod network_character_controller;
 pub mod character_controller;
 pub mod instant_event_buffer;
+pub mod server_recorder;
+pub mod client_demo;
+pub mod camera;
+pub mod input_mapping;
+pub mod user_data;
+pub mod room;
+pub mod admin_console;
+pub mod server_metrics;
+pub mod chat;
+pub mod spawn;
+pub mod props;
+pub mod pawn;
+pub mod interaction;
+pub mod health;
+pub mod projectile;
+pub mod auth;
+pub mod access_list;
 
 use config::PHYSICS_FIXED_TICK_RATE64;
+use chat::{ChatMessage, ChatBroadcast};
+use spawn::CharacterRespawned;
+use props::{Prop, NetworkProp};
+use interaction::{Interactable, InteractionState, NetworkInteractable, InteractRequest};
+use health::{Health, Dead, CharacterDied};
+use projectile::{Projectile, FireProjectile};
 use network_character_controller::NetworkCharacterControllerPlugin;
 use serde::{Serialize, Deserialize};
 use bevy::prelude::*;
@@ -21,6 +44,8 @@ pub const AFTER_PHYSICS_SET: PhysicsSet = PhysicsSet::Sync;
 pub const CHARACTER_HIGHT: f32 = 1.0;
 pub const CHARACTER_OFFSET: f32 = 0.1;
 pub const CHARACTER_RADIUS: f32 = 0.5;
+// above the character center, where interactions and shots start
+pub const CHARACTER_EYE_HEIGHT: f32 = CHARACTER_HIGHT * 0.5 + CHARACTER_OFFSET;
 pub const CHARACTER_SPAWN_POSITION: Vec3 = Vec3::new(0.0, 2.0, 0.0);
 pub const CHARACTER_COLOR: Color = Color::RED;
 pub const CHARACTER_LINEAR_SPEED: f32 = 10.0;
@@ -48,28 +73,66 @@ impl NetworkId {
 }
 
 #[derive(Event, Serialize, Deserialize, Default, Clone)]
-struct NetworkAction {
+pub struct NetworkAction {
     pub linear: Vec2,
     pub angular: Vec2,
-    pub jump: bool
+    pub jump: bool,
+    // held rise minus held descend, used while swimming
+    pub vertical: f32
+}
+
+// counts completed FixedUpdate steps
+// server and client keep their own counter
+#[derive(Resource, Default)]
+pub struct SimulationTick(u32);
+
+impl SimulationTick {
+    #[inline]
+    pub fn get(&self) -> u32 {
+        self.0
+    }
 }
 
 pub struct GameCommonPlugin;
 
 impl Plugin for GameCommonPlugin {
     fn build(&self, app: &mut App) {
+        // exactly one step per FixedUpdate, Fixed would accumulate real time
+        // and step differently in a replay
         app.insert_resource(
-            Time::new_with(Physics::fixed_hz(PHYSICS_FIXED_TICK_RATE64))
+            Time::new_with(Physics::fixed_once_hz(PHYSICS_FIXED_TICK_RATE64))
         )
         .add_plugins((
             PhysicsPlugins::new(FixedUpdate),
             NetworkCharacterControllerPlugin
         ))
+        .init_resource::<SimulationTick>()
+        .add_systems(FixedUpdate, 
+            increment_simulation_tick
+            .after(AFTER_PHYSICS_SET)
+        )
         .replicate::<NetworkId>()
-        .add_client_event::<NetworkAction>(ChannelKind::Unreliable);
+        .replicate::<Prop>()
+        .replicate::<NetworkProp>()
+        .replicate::<Interactable>()
+        .replicate::<InteractionState>()
+        .replicate::<NetworkInteractable>()
+        .replicate::<Health>()
+        .replicate::<Dead>()
+        .replicate::<Projectile>()
+        .add_client_event::<ChatMessage>(ChannelKind::Ordered)
+        .add_server_event::<ChatBroadcast>(ChannelKind::Ordered)
+        .add_client_event::<InteractRequest>(ChannelKind::Ordered)
+        .add_client_event::<FireProjectile>(ChannelKind::Ordered)
+        .add_server_event::<CharacterRespawned>(ChannelKind::Ordered)
+        .add_server_event::<CharacterDied>(ChannelKind::Ordered);
     }
 }
 
+fn increment_simulation_tick(mut tick: ResMut<SimulationTick>) {
+    tick.0 = tick.0.wrapping_add(1);
+}
+
 #[inline]
 pub fn yaw_to_quat(y: f32) -> Quat {
     Quat::from_rotation_y(y)
@@ -79,3 +142,9 @@ pub fn yaw_to_quat(y: f32) -> Quat {
 pub fn quat_to_yaw(q: Quat) -> f32 {
     q.to_euler(EulerRot::YXZ).0
 }
+
+// horizontal facing of a character rotation
+#[inline]
+pub fn facing(q: Quat) -> Vec3 {
+    yaw_to_quat(quat_to_yaw(q)) * Vec3::NEG_Z
+}
@@ -1,19 +1,96 @@
 use serde::{Serialize, Deserialize};
 use bevy::prelude::*;
 use bevy_replicon::prelude::*;
-use crate::character_controller::CharacterControllerPlugin;
+use crate::{
+    NetworkAction,
+    quat_to_yaw,
+    character_controller::*,
+    instant_event_buffer::InstantEvents,
+    health::Dead,
+    pawn::*
+};
+
+// float error of clamped vectors from client
+const MOVE_INPUT_TOLERANCE: f32 = 0.001;
 
 #[derive(Component, Serialize, Deserialize, Default)]
 pub struct NetworkCharacterController {
     pub translation: Vec3,
-    pub yaw: f32
+    pub yaw: f32,
+    pub swimming: bool,
+    pub climbing: bool
+}
+
+// walking capsule driven by NetworkAction
+#[derive(Component, Serialize, Deserialize, Default)]
+pub struct CharacterPawn;
+
+impl NetworkPawn for CharacterPawn {
+    type Input = NetworkAction;
+    type State = NetworkCharacterController;
+    type StateSource = (&'static Transform, Has<Swimming>, Has<Climbing>);
+
+    fn validate_input(client_id: ClientId, input: NetworkAction) -> Option<NetworkAction> {
+        // debug only, a bad client sends one every tick
+        if input.linear.length_squared() > 1.0 + MOVE_INPUT_TOLERANCE {
+            debug!(
+                "client: {client_id:?} sent over length move input: {}",
+                input.linear
+            );
+        }
+        // length is clamped when converted to ControllerAction
+        Some(input)
+    }
+
+    fn write_state(
+        (transform, is_swimming, is_climbing): (&Transform, bool, bool),
+        mut state: Mut<NetworkCharacterController>
+    ) {
+        state.translation = transform.translation;
+        state.yaw = quat_to_yaw(transform.rotation);
+        state.swimming = is_swimming;
+        state.climbing = is_climbing;
+    }
 }
 
 pub struct NetworkCharacterControllerPlugin;
 
 impl Plugin for NetworkCharacterControllerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(CharacterControllerPlugin)
-        .replicate::<NetworkCharacterController>();
+        app.add_plugins((
+            CharacterControllerPlugin,
+            NetworkPawnPlugin::<CharacterPawn>::new(ChannelKind::Unreliable)
+        ))
+        .add_systems(FixedUpdate,
+            apply_character_action
+            .in_set(PawnSet::Apply)
+        );
+    }
+}
+
+// server for every character, client for the local one
+// actions of dead characters are dropped
+fn apply_character_action(
+    mut actions: InstantEvents<NetworkAction>,
+    mut controls: InstantEvents<ControllerAction>,
+    dead: Query<(), With<Dead>>
+) {
+    let tick = actions.tick();
+    for (e, mut buffer) in actions.iter_mut() {
+        let is_dead = dead.contains(e);
+        for (_, a) in buffer.read(tick) {
+            if is_dead {
+                continue;
+            }
+            if let Some(m) = ControllerAction::from_move_input(a.linear) {
+                controls.send(e, m);
+            }
+            if a.jump {
+                controls.send(e, ControllerAction::Jump);
+            }
+            if let Some(s) = ControllerAction::from_swim_input(a.vertical) {
+                controls.send(e, s);
+            }
+        }
     }
 }
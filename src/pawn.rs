@@ -0,0 +1,151 @@
+// generic networked pawn
+// one path for inputs from client to server and state from server to clients
+// a pawn kind is a marker component implementing NetworkPawn,
+// see CharacterPawn for the walking capsule
+
+use std::marker::PhantomData;
+use bevy::{
+    prelude::*,
+    ecs::{
+        query::{ReadOnlyQueryData, ROQueryItem},
+        system::SystemParam
+    }
+};
+use bevy_replicon::prelude::*;
+use serde::{Serialize, de::DeserializeOwned};
+use crate::{
+    *,
+    instant_event_buffer::InstantEventBuffer
+};
+
+pub trait NetworkPawn: Component + Serialize + DeserializeOwned {
+    // sent by owning client every fixed tick
+    type Input: Event + Serialize + DeserializeOwned + Clone;
+    // replicated to every client
+    type State: Component + Serialize + DeserializeOwned;
+    // simulation data the state is written from
+    type StateSource: ReadOnlyQueryData;
+
+    // input comes from network, never trust it
+    // None drops the input
+    fn validate_input(client_id: ClientId, input: Self::Input) -> Option<Self::Input>;
+
+    // server only, before replication
+    fn write_state(source: ROQueryItem<'_, Self::StateSource>, state: Mut<Self::State>);
+}
+
+// fixed update, before physics
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum PawnSet {
+    // server routes inputs from clients, client writes local input
+    Input,
+    // pawn kinds turn buffered inputs into simulation
+    Apply
+}
+
+// input buffer is read by the pawn kind in PawnSet::Apply
+// on client, only the local pawn has the buffer
+#[derive(Bundle)]
+pub struct NetworkPawnBundle<P: NetworkPawn> {
+    pawn: P,
+    state: P::State,
+    input_buffer: InstantEventBuffer<P::Input>
+}
+
+impl<P: NetworkPawn> NetworkPawnBundle<P> {
+    #[inline]
+    pub fn new(pawn: P, state: P::State) -> Self {
+        Self {
+            pawn,
+            state,
+            input_buffer: InstantEventBuffer::new()
+        }
+    }
+}
+
+// both server and client, server systems run only with RepliconServer
+pub struct NetworkPawnPlugin<P: NetworkPawn> {
+    input_channel: ChannelKind,
+    _marker: PhantomData<fn() -> P>
+}
+
+impl<P: NetworkPawn> NetworkPawnPlugin<P> {
+    #[inline]
+    pub fn new(input_channel: ChannelKind) -> Self {
+        Self {
+            input_channel,
+            _marker: PhantomData
+        }
+    }
+}
+
+impl<P: NetworkPawn> Plugin for NetworkPawnPlugin<P> {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(FixedUpdate, (
+            PawnSet::Input,
+            PawnSet::Apply
+        ).chain(
+        ).before(BEFORE_PHYSICS_SET))
+        .replicate::<P>()
+        .replicate::<P::State>()
+        .add_client_event::<P::Input>(self.input_channel)
+        .add_systems(FixedUpdate,
+            route_pawn_input::<P>
+            .in_set(PawnSet::Input)
+            .run_if(resource_exists::<RepliconServer>)
+        )
+        .add_systems(PostUpdate,
+            write_pawn_state::<P>
+            .before(ServerSet::Send)
+            .run_if(resource_exists::<RepliconServer>)
+        );
+    }
+}
+
+fn route_pawn_input<P: NetworkPawn>(
+    mut inputs: EventReader<FromClient<P::Input>>,
+    mut pawns: Query<(&NetworkId, &mut InstantEventBuffer<P::Input>), With<P>>,
+    tick: Res<SimulationTick>
+) {
+    for FromClient { client_id, event } in inputs.read() {
+        let Some(input) = P::validate_input(*client_id, event.clone()) else {
+            continue;
+        };
+        for (net_id, mut buffer) in pawns.iter_mut() {
+            if net_id.client_id() == *client_id {
+                buffer.send(tick.get(), input.clone());
+            }
+        }
+    }
+}
+
+fn write_pawn_state<P: NetworkPawn>(
+    mut pawns: Query<(P::StateSource, &mut P::State), With<P>>
+) {
+    for (source, state) in pawns.iter_mut() {
+        P::write_state(source, state);
+    }
+}
+
+// client side, sends input to server and buffers it for local prediction
+#[derive(SystemParam)]
+pub struct PawnInputWriter<'w, 's, P: NetworkPawn> {
+    local: Query<'w, 's, &'static mut InstantEventBuffer<<P as NetworkPawn>::Input>, With<P>>,
+    inputs: EventWriter<'w, <P as NetworkPawn>::Input>,
+    tick: Res<'w, SimulationTick>
+}
+
+impl<'w, 's, P: NetworkPawn> PawnInputWriter<'w, 's, P> {
+    // false until the local pawn is spawned
+    #[inline]
+    pub fn has_local_pawn(&self) -> bool {
+        !self.local.is_empty()
+    }
+
+    pub fn send(&mut self, input: P::Input) {
+        if let Ok(mut buffer) = self.local.get_single_mut() {
+            buffer.send(self.tick.get(), input.clone());
+        }
+        self.inputs.send(input);
+    }
+}
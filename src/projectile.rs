@@ -0,0 +1,486 @@
+// server authoritative projectiles fired from the character facing
+// hitscan is resolved at once, travelling ones are stepped by ray per fixed tick,
+// both hit level and characters and damage anything with Health
+// the firing client draws a predicted projectile at once, tagged with a prediction id,
+// and hands its flight over to the replicated one carrying the same id
+
+use bevy::{
+    prelude::*,
+    utils::HashMap
+};
+use bevy_replicon::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::{
+    *,
+    room::RoomId,
+    health::{Health, Dead, Damage, DamageSource},
+    input_mapping::{ActionInput, InputAction},
+    game_client::LocalCharacter,
+    client_builder::Client
+};
+
+pub const PROJECTILE_SPEED: f32 = 40.0;
+pub const PROJECTILE_RANGE: f32 = 80.0;
+pub const PROJECTILE_DAMAGE: f32 = 25.0;
+pub const HITSCAN_RANGE: f32 = 60.0;
+pub const HITSCAN_DAMAGE: f32 = 15.0;
+// in fixed ticks, per client for both kinds
+pub const FIRE_INTERVAL_TICKS: u32 = 16;
+// out of the character capsule, so shots never start inside the shooter
+pub const MUZZLE_CLEARANCE: f32 = 0.1;
+// replicated tracer lives a few network ticks, so every client receives it
+pub const HITSCAN_TRACER_TICKS: u32 = 16;
+// seconds, drawn this long on clients
+pub const HITSCAN_TRACER_LIFETIME: f32 = 0.15;
+// seconds, unconfirmed predictions are dropped, like shots rejected by cooldown
+pub const PREDICTION_TIMEOUT: f32 = 1.0;
+pub const PROJECTILE_RADIUS: f32 = 0.1;
+pub const PROJECTILE_COLOR: Color = Color::YELLOW;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProjectileKind {
+    Hitscan,
+    Travelling
+}
+
+impl ProjectileKind {
+    #[inline]
+    pub fn damage(&self) -> f32 {
+        match self {
+            ProjectileKind::Hitscan => HITSCAN_DAMAGE,
+            ProjectileKind::Travelling => PROJECTILE_DAMAGE
+        }
+    }
+}
+
+// client to server, origin and direction are taken from the server character
+#[derive(Event, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct FireProjectile {
+    pub kind: ProjectileKind,
+    pub prediction_id: u32
+}
+
+// replicated, fixed for the whole flight
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Projectile {
+    pub kind: ProjectileKind,
+    pub owner: ClientId,
+    pub prediction_id: u32,
+    pub origin: Vec3,
+    pub direction: Vec3,
+    // hitscan to what it hit, travelling to the end of its range
+    pub reach: f32
+}
+
+// start of a shot from a character transform
+#[inline]
+pub fn muzzle(transform: &Transform) -> (Vec3, Vec3) {
+    let direction = facing(transform.rotation);
+    let origin = transform.translation
+    + Vec3::Y * CHARACTER_EYE_HEIGHT
+    + direction * (CHARACTER_RADIUS + MUZZLE_CLEARANCE);
+    (origin, direction)
+}
+
+// nearest non sensor hit along the segment, as distance and entity
+pub fn cast_shot(
+    spatial_query: &SpatialQuery,
+    origin: Vec3,
+    direction: Vec3,
+    distance: f32,
+    filter: SpatialQueryFilter,
+    sensors: &Query<(), With<Sensor>>
+) -> Option<(f32, Entity)> {
+    let direction = Direction3d::new(direction).ok()?;
+    spatial_query.cast_ray_predicate(
+        origin,
+        direction,
+        distance,
+        true,
+        filter,
+        &|e| !sensors.contains(e)
+    )
+    .map(|hit| (hit.time_of_impact, hit.entity))
+}
+
+// server side
+#[derive(Component)]
+struct ProjectileFlight {
+    filter: SpatialQueryFilter,
+    traveled: f32,
+    // hitscan tracer, counted down in fixed ticks
+    ticks_left: u32
+}
+
+// last fire tick per client
+#[derive(Resource, Default)]
+struct FireCooldowns(HashMap<ClientId, u32>);
+
+pub struct ProjectileServerPlugin;
+
+impl Plugin for ProjectileServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FireCooldowns>()
+        .add_systems(PreUpdate,
+            remove_fire_cooldown
+            .after(ServerSet::Receive)
+        )
+        // recorded with actions, so replay fires in the same tick
+        .add_systems(FixedUpdate,
+            handle_fire_projectile
+            .before(BEFORE_PHYSICS_SET)
+        )
+        .add_systems(FixedUpdate,
+            projectile_flight_system
+            .after(AFTER_PHYSICS_SET)
+        );
+    }
+}
+
+fn remove_fire_cooldown(
+    mut events: EventReader<ServerEvent>,
+    mut cooldowns: ResMut<FireCooldowns>
+) {
+    for e in events.read() {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = e {
+            cooldowns.0.remove(client_id);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_fire_projectile(
+    mut commands: Commands,
+    mut requests: EventReader<FromClient<FireProjectile>>,
+    mut cooldowns: ResMut<FireCooldowns>,
+    mut damage: EventWriter<Damage>,
+    characters: Query<(Entity, &NetworkId, &Transform, &CollisionLayers, &RoomId), Without<Dead>>,
+    health: Query<(), With<Health>>,
+    sensors: Query<(), With<Sensor>>,
+    spatial_query: SpatialQuery,
+    tick: Res<SimulationTick>
+) {
+    let tick = tick.get();
+    for FromClient { client_id, event: request } in requests.read() {
+        let Some((e, _, transform, layers, room)) = characters.iter()
+        .find(|(_, net_id, ..)| net_id.client_id() == *client_id) else {
+            continue;
+        };
+        // wrapping, same as SimulationTick
+        let cooling = cooldowns.0.get(client_id)
+        .is_some_and(|last| tick.wrapping_sub(*last) < FIRE_INTERVAL_TICKS);
+        if cooling {
+            debug!("client: {client_id:?} fired too fast");
+            continue;
+        }
+        cooldowns.0.insert(*client_id, tick);
+
+        let (origin, direction) = muzzle(transform);
+        // own room only, never the shooter
+        let filter = SpatialQueryFilter::from_mask(layers.filters)
+        .with_excluded_entities([e]);
+
+        let reach = match request.kind {
+            ProjectileKind::Hitscan => {
+                let hit = cast_shot(
+                    &spatial_query,
+                    origin,
+                    direction,
+                    HITSCAN_RANGE,
+                    filter.clone(),
+                    &sensors
+                );
+                if let Some((_, target)) = hit.filter(|(_, target)| health.contains(*target)) {
+                    damage.send(Damage {
+                        target,
+                        amount: request.kind.damage(),
+                        source: DamageSource::Client(*client_id)
+                    });
+                }
+                hit.map_or(HITSCAN_RANGE, |(toi, _)| toi)
+            }
+            ProjectileKind::Travelling => PROJECTILE_RANGE
+        };
+
+        commands.spawn((
+            Replicated,
+            *room,
+            Projectile {
+                kind: request.kind,
+                owner: *client_id,
+                prediction_id: request.prediction_id,
+                origin,
+                direction,
+                reach
+            },
+            ProjectileFlight {
+                filter,
+                traveled: 0.0,
+                ticks_left: HITSCAN_TRACER_TICKS
+            }
+        ));
+    }
+}
+
+fn projectile_flight_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &Projectile, &mut ProjectileFlight)>,
+    mut damage: EventWriter<Damage>,
+    health: Query<(), With<Health>>,
+    sensors: Query<(), With<Sensor>>,
+    spatial_query: SpatialQuery,
+    fixed_time: Res<Time<Fixed>>
+) {
+    // tick rate can be changed by admin, speed stays per second
+    let delta = fixed_time.delta_seconds();
+    for (e, projectile, mut flight) in query.iter_mut() {
+        if projectile.kind == ProjectileKind::Hitscan {
+            flight.ticks_left = flight.ticks_left.saturating_sub(1);
+            if flight.ticks_left == 0 {
+                commands.entity(e).despawn_recursive();
+            }
+            continue;
+        }
+
+        let step = (PROJECTILE_SPEED * delta)
+        .min(projectile.reach - flight.traveled);
+        let from = projectile.origin + projectile.direction * flight.traveled;
+        let hit = cast_shot(
+            &spatial_query,
+            from,
+            projectile.direction,
+            step,
+            flight.filter.clone(),
+            &sensors
+        );
+        let Some((toi, target)) = hit else {
+            flight.traveled += step;
+            if flight.traveled >= projectile.reach {
+                commands.entity(e).despawn_recursive();
+            }
+            continue;
+        };
+
+        if health.contains(target) {
+            damage.send(Damage {
+                target,
+                amount: projectile.kind.damage(),
+                source: DamageSource::Client(projectile.owner)
+            });
+        }
+        commands.entity(e).despawn_recursive();
+        debug!("projectile of client: {:?} hit: {target:?} after: {}", projectile.owner, flight.traveled + toi);
+    }
+}
+
+// client side flight of predicted and replicated projectiles
+#[derive(Component, Clone, Copy)]
+pub struct ProjectileVisual {
+    kind: ProjectileKind,
+    origin: Vec3,
+    direction: Vec3,
+    traveled: f32,
+    reach: f32,
+    age: f32
+}
+
+impl ProjectileVisual {
+    #[inline]
+    fn new(kind: ProjectileKind, origin: Vec3, direction: Vec3, reach: f32) -> Self {
+        Self {
+            kind,
+            origin,
+            direction,
+            traveled: 0.0,
+            reach,
+            age: 0.0
+        }
+    }
+
+    #[inline]
+    pub fn position(&self) -> Vec3 {
+        self.origin + self.direction * self.traveled
+    }
+}
+
+// drawn until the replicated projectile with the same id arrives
+#[derive(Component)]
+pub struct PredictedProjectile {
+    pub prediction_id: u32
+}
+
+#[derive(Resource, Default)]
+struct NextPredictionId(u32);
+
+pub struct ProjectileClientPlugin;
+
+impl Plugin for ProjectileClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NextPredictionId>()
+        .add_systems(PreUpdate,
+            reconcile_projectile_system
+            .after(ClientSet::Receive)
+        )
+        .add_systems(Update, (
+            fire_projectile_system,
+            projectile_visual_system,
+            expire_predicted_projectile_system,
+            draw_projectile_system
+        ).chain());
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn fire_projectile_system(
+    mut commands: Commands,
+    characters: Query<(Entity, &Transform), (With<LocalCharacter>, Without<Dead>)>,
+    input: ActionInput,
+    mut requests: EventWriter<FireProjectile>,
+    mut next_id: ResMut<NextPredictionId>,
+    mut last_fire: Local<Option<f32>>,
+    sensors: Query<(), With<Sensor>>,
+    spatial_query: SpatialQuery,
+    fixed_time: Res<Time<Fixed>>,
+    time: Res<Time<Real>>
+) {
+    let kind = if input.just_pressed(InputAction::Fire) {
+        ProjectileKind::Travelling
+    } else if input.just_pressed(InputAction::FireHitscan) {
+        ProjectileKind::Hitscan
+    } else {
+        return;
+    };
+    let Ok((e, transform)) = characters.get_single() else {
+        return;
+    };
+    // same interval as server, a rejected shot only times out
+    let now = time.elapsed_seconds();
+    let interval = FIRE_INTERVAL_TICKS as f32 * fixed_time.timestep().as_secs_f32();
+    if last_fire.is_some_and(|last| now - last < interval) {
+        return;
+    }
+    *last_fire = Some(now);
+
+    let (origin, direction) = muzzle(transform);
+    let reach = match kind {
+        ProjectileKind::Hitscan => cast_shot(
+            &spatial_query,
+            origin,
+            direction,
+            HITSCAN_RANGE,
+            SpatialQueryFilter::from_excluded_entities([e]),
+            &sensors
+        )
+        .map_or(HITSCAN_RANGE, |(toi, _)| toi),
+        ProjectileKind::Travelling => PROJECTILE_RANGE
+    };
+
+    let prediction_id = next_id.0;
+    next_id.0 = next_id.0.wrapping_add(1);
+    commands.spawn((
+        PredictedProjectile { prediction_id },
+        ProjectileVisual::new(kind, origin, direction, reach)
+    ));
+    requests.send(FireProjectile { kind, prediction_id });
+}
+
+// own projectiles continue from the predicted flight, so nothing jumps back
+fn reconcile_projectile_system(
+    mut commands: Commands,
+    query: Query<(Entity, &Projectile), Added<Projectile>>,
+    predicted: Query<(Entity, &PredictedProjectile, &ProjectileVisual)>,
+    client: Res<Client>
+) {
+    for (e, projectile) in query.iter() {
+        let mut visual = ProjectileVisual::new(
+            projectile.kind,
+            projectile.origin,
+            projectile.direction,
+            projectile.reach
+        );
+
+        if projectile.owner.get() == client.id() {
+            if let Some((p, _, predicted_visual)) = predicted.iter()
+            .find(|(_, p, _)| p.prediction_id == projectile.prediction_id) {
+                visual.traveled = predicted_visual.traveled.min(projectile.reach);
+                visual.age = predicted_visual.age;
+                commands.entity(p).despawn_recursive();
+            }
+        }
+        commands.entity(e).insert(visual);
+    }
+}
+
+// stops at level geometry, the server despawns it on a hit
+fn projectile_visual_system(
+    mut query: Query<&mut ProjectileVisual>,
+    sensors: Query<(), With<Sensor>>,
+    spatial_query: SpatialQuery,
+    time: Res<Time>
+) {
+    let delta = time.delta_seconds();
+    for mut visual in query.iter_mut() {
+        visual.age += delta;
+        if visual.kind == ProjectileKind::Hitscan || visual.traveled >= visual.reach {
+            continue;
+        }
+
+        let step = (PROJECTILE_SPEED * delta).min(visual.reach - visual.traveled);
+        let hit = cast_shot(
+            &spatial_query,
+            visual.position(),
+            visual.direction,
+            step,
+            SpatialQueryFilter::default(),
+            &sensors
+        );
+        match hit {
+            Some((toi, _)) => {
+                visual.traveled += toi;
+                visual.reach = visual.traveled;
+            }
+            None => visual.traveled += step
+        }
+    }
+}
+
+fn expire_predicted_projectile_system(
+    mut commands: Commands,
+    query: Query<(Entity, &ProjectileVisual), With<PredictedProjectile>>
+) {
+    for (e, visual) in query.iter() {
+        if visual.age > PREDICTION_TIMEOUT {
+            commands.entity(e).despawn_recursive();
+        }
+    }
+}
+
+fn draw_projectile_system(
+    query: Query<&ProjectileVisual>,
+    mut gizmos: Gizmos
+) {
+    for visual in query.iter() {
+        match visual.kind {
+            ProjectileKind::Hitscan => {
+                if visual.age <= HITSCAN_TRACER_LIFETIME {
+                    gizmos.line(
+                        visual.origin,
+                        visual.origin + visual.direction * visual.reach,
+                        PROJECTILE_COLOR
+                    );
+                }
+            }
+            ProjectileKind::Travelling => {
+                if visual.traveled < visual.reach {
+                    gizmos.sphere(
+                        visual.position(),
+                        Quat::IDENTITY,
+                        PROJECTILE_RADIUS,
+                        PROJECTILE_COLOR
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+// dynamic props simulated on server
+// characters push them on contact, see PushStrength in character controller
+// transform is replicated only while the body is awake,
+// clients interpolate between received transforms
+
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::config::DEV_NETWORK_TICK_DELTA;
+
+pub const PROP_CRATE_SIZE: f32 = 1.5;
+pub const PROP_CRATE_COLOR: Color = Color::rgb(0.6, 0.4, 0.2);
+pub const PROP_BALL_RADIUS: f32 = 0.6;
+pub const PROP_BALL_COLOR: Color = Color::YELLOW;
+
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Prop {
+    Crate,
+    Ball
+}
+
+impl Prop {
+    #[inline]
+    pub fn collider(&self) -> Collider {
+        match self {
+            Prop::Crate => Collider::cuboid(PROP_CRATE_SIZE, PROP_CRATE_SIZE, PROP_CRATE_SIZE),
+            Prop::Ball => Collider::sphere(PROP_BALL_RADIUS)
+        }
+    }
+
+    #[inline]
+    pub fn mesh(&self) -> Mesh {
+        match self {
+            Prop::Crate => Mesh::from(Cuboid::from_size(Vec3::splat(PROP_CRATE_SIZE))),
+            Prop::Ball => Mesh::from(Sphere::new(PROP_BALL_RADIUS))
+        }
+    }
+
+    #[inline]
+    pub fn color(&self) -> Color {
+        match self {
+            Prop::Crate => PROP_CRATE_COLOR,
+            Prop::Ball => PROP_BALL_COLOR
+        }
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Default, PartialEq)]
+pub struct NetworkProp {
+    pub translation: Vec3,
+    pub rotation: Quat
+}
+
+// server side, extra is added like level entities
+pub fn server_spawn_prop(
+    commands: &mut Commands,
+    prop: Prop,
+    position: Vec3,
+    extra: impl Bundle
+) {
+    commands.spawn((
+        Replicated,
+        prop,
+        NetworkProp {
+            translation: position,
+            rotation: Quat::IDENTITY
+        },
+        TransformBundle::from_transform(
+            Transform::from_translation(position)
+        ),
+        RigidBody::Dynamic,
+        prop.collider(),
+        extra
+    ));
+}
+
+pub struct PropServerPlugin;
+
+impl Plugin for PropServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostUpdate,
+            update_network_prop_system
+            .before(ServerSet::Send)
+        );
+    }
+}
+
+// sleeping bodies keep the last value, so nothing is sent for them
+fn update_network_prop_system(
+    mut query: Query<(&Transform, &mut NetworkProp), Without<Sleeping>>
+) {
+    for (transform, mut net_prop) in query.iter_mut() {
+        net_prop.set_if_neq(NetworkProp {
+            translation: transform.translation,
+            rotation: transform.rotation
+        });
+    }
+}
+
+#[derive(Component)]
+pub struct PropInterpolation {
+    from: Transform,
+    to: Transform,
+    t: f32
+}
+
+pub struct PropClientPlugin;
+
+impl Plugin for PropClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, (
+            handle_prop_spawn,
+            receive_network_prop
+        ).chain(
+        ).after(ClientSet::Receive))
+        .add_systems(Update, interpolate_prop_system);
+    }
+}
+
+fn handle_prop_spawn(
+    mut commands: Commands,
+    query: Query<(Entity, &Prop, &NetworkProp), Added<Prop>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>
+) {
+    for (e, prop, net_prop) in query.iter() {
+        let transform = Transform {
+            translation: net_prop.translation,
+            rotation: net_prop.rotation,
+            ..default()
+        };
+        // kinematic on client, local character collides but never pushes
+        commands.entity(e)
+        .insert((
+            PbrBundle {
+                mesh: meshes.add(prop.mesh()),
+                material: materials.add(prop.color()),
+                transform,
+                ..default()
+            },
+            RigidBody::Kinematic,
+            prop.collider(),
+            PropInterpolation {
+                from: transform,
+                to: transform,
+                t: 1.0
+            }
+        ));
+    }
+}
+
+fn receive_network_prop(
+    mut query: Query<(
+        &NetworkProp,
+        &Transform,
+        &mut PropInterpolation
+    ), Changed<NetworkProp>>
+) {
+    for (net_prop, transform, mut interpolation) in query.iter_mut() {
+        interpolation.from = *transform;
+        interpolation.to = Transform {
+            translation: net_prop.translation,
+            rotation: net_prop.rotation,
+            ..default()
+        };
+        interpolation.t = 0.0;
+    }
+}
+
+// reaches received transform in one network tick
+fn interpolate_prop_system(
+    mut query: Query<(&mut Transform, &mut PropInterpolation)>,
+    time: Res<Time>
+) {
+    for (mut transform, mut interpolation) in query.iter_mut() {
+        if interpolation.t >= 1.0 {
+            continue;
+        }
+        interpolation.t = (interpolation.t + time.delta_seconds() / DEV_NETWORK_TICK_DELTA)
+        .min(1.0);
+        let t = interpolation.t;
+        transform.translation = interpolation.from.translation
+        .lerp(interpolation.to.translation, t);
+        transform.rotation = interpolation.from.rotation
+        .slerp(interpolation.to.rotation, t);
+    }
+}
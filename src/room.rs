@@ -0,0 +1,291 @@
+// isolated game rooms in one server process
+// each room owns one collision layer, so rooms never touch in physics
+// and replicated entities are only visible to members of the same room
+
+use bevy::{
+    prelude::*,
+    ecs::query::QueryItem,
+    utils::HashMap
+};
+use bevy_replicon::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::{
+    *,
+    level::{server_spawn_level, server_spawn_props, server_spawn_interactables},
+    spawn::SpawnSelector,
+    character_controller::FallTracker
+};
+
+// one collision layer per room
+pub const MAX_ROOMS: usize = 32;
+pub const DEFAULT_ROOM: RoomId = RoomId(0);
+
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct RoomId(pub u32);
+
+#[derive(Event, Clone, Copy)]
+pub struct CreateRoom(pub RoomId);
+
+// members are moved to DEFAULT_ROOM, which can not be destroyed
+#[derive(Event, Clone, Copy)]
+pub struct DestroyRoom(pub RoomId);
+
+#[derive(Event, Clone, Copy)]
+pub struct MoveToRoom {
+    pub client_id: ClientId,
+    pub room: RoomId
+}
+
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub struct RoomSet;
+
+#[derive(Resource, Default)]
+pub struct Rooms {
+    layers: HashMap<RoomId, u32>,
+    members: HashMap<ClientId, RoomId>,
+    // clients need full visibility update
+    dirty: Vec<ClientId>
+}
+
+impl Rooms {
+    #[inline]
+    pub fn contains(&self, room: RoomId) -> bool {
+        self.layers.contains_key(&room)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = RoomId> + '_ {
+        self.layers.keys().copied()
+    }
+
+    #[inline]
+    pub fn room_of(&self, client_id: ClientId) -> Option<RoomId> {
+        self.members.get(&client_id).copied()
+    }
+
+    #[inline]
+    pub fn members(&self, room: RoomId) -> impl Iterator<Item = ClientId> + '_ {
+        self.members.iter()
+        .filter(move |(_, r)| **r == room)
+        .map(|(c, _)| *c)
+    }
+
+    #[inline]
+    pub fn layers(&self, room: RoomId) -> Option<CollisionLayers> {
+        self.layers.get(&room)
+        .map(|bit| CollisionLayers::from_bits(1 << bit, 1 << bit))
+    }
+
+    // requested room if it exists, otherwise DEFAULT_ROOM
+    pub fn join(&mut self, client_id: ClientId, requested: RoomId) -> RoomId {
+        let room = if self.contains(requested) {
+            requested
+        } else {
+            warn!("room: {requested:?} does not exist, client: {client_id:?} joins default room");
+            DEFAULT_ROOM
+        };
+        self.members.insert(client_id, room);
+        self.dirty.push(client_id);
+        room
+    }
+
+    #[inline]
+    pub fn leave(&mut self, client_id: ClientId) -> Option<RoomId> {
+        self.members.remove(&client_id)
+    }
+
+    fn allocate(&mut self, room: RoomId) -> Option<CollisionLayers> {
+        if self.contains(room) {
+            return None;
+        }
+        let bit = (0..MAX_ROOMS as u32)
+        .find(|bit| !self.layers.values().any(|b| b == bit))?;
+        self.layers.insert(room, bit);
+        self.layers(room)
+    }
+}
+
+pub struct RoomPlugin;
+
+impl Plugin for RoomPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rooms>()
+        .add_event::<CreateRoom>()
+        .add_event::<DestroyRoom>()
+        .add_event::<MoveToRoom>()
+        .add_systems(Startup, setup_default_room)
+        .add_systems(PreUpdate, (
+            create_room_system,
+            move_to_room_system,
+            destroy_room_system
+        ).chain(
+        ).in_set(RoomSet
+        ).after(ServerSet::Receive))
+        .add_systems(PostUpdate,
+            room_visibility_system
+            .before(ServerSet::Send)
+        );
+    }
+}
+
+fn create_room(commands: &mut Commands, rooms: &mut Rooms, room: RoomId) {
+    let Some(layers) = rooms.allocate(room) else {
+        warn!("room: {room:?} already exists or no collision layer is left");
+        return;
+    };
+
+    server_spawn_level(commands, (room, layers));
+    server_spawn_props(commands, (room, layers));
+    server_spawn_interactables(commands, (room, layers));
+    info!("room: {room:?} created");
+}
+
+fn setup_default_room(
+    mut commands: Commands,
+    mut rooms: ResMut<Rooms>
+) {
+    create_room(&mut commands, &mut rooms, DEFAULT_ROOM);
+}
+
+fn create_room_system(
+    mut commands: Commands,
+    mut rooms: ResMut<Rooms>,
+    mut events: EventReader<CreateRoom>
+) {
+    for CreateRoom(room) in events.read() {
+        create_room(&mut commands, &mut rooms, *room);
+    }
+}
+
+type RoomCharacter = (
+    &'static NetworkId,
+    &'static mut RoomId,
+    &'static mut CollisionLayers,
+    &'static mut ShapeCaster,
+    &'static mut Position,
+    &'static mut LinearVelocity,
+    &'static mut FallTracker
+);
+
+fn move_character(
+    (_, mut room_id, mut layers, mut caster, mut pos, mut vel, mut fall): QueryItem<RoomCharacter>,
+    room: RoomId,
+    room_layers: CollisionLayers,
+    position: Vec3
+) {
+    *room_id = room;
+    *layers = room_layers;
+    caster.query_filter = SpatialQueryFilter::from_mask(room_layers.filters);
+    pos.0 = position;
+    vel.0 = Vec3::ZERO;
+    fall.reset();
+}
+
+// positions of characters already in the room
+#[inline]
+fn room_positions(query: &Query<RoomCharacter>, room: RoomId) -> Vec<Vec3> {
+    query.iter()
+    .filter(|(_, r, ..)| **r == room)
+    .map(|(.., pos, _, _)| pos.0)
+    .collect()
+}
+
+fn move_to_room_system(
+    mut query: Query<RoomCharacter>,
+    mut rooms: ResMut<Rooms>,
+    mut events: EventReader<MoveToRoom>,
+    mut selector: SpawnSelector
+) {
+    for MoveToRoom { client_id, room } in events.read() {
+        if rooms.room_of(*client_id).is_none() {
+            warn!("client: {client_id:?} is not in any room");
+            continue;
+        }
+        let room = rooms.join(*client_id, *room);
+        let Some(room_layers) = rooms.layers(room) else {
+            continue;
+        };
+
+        let others = room_positions(&query, room);
+        let position = selector.select(room, room_layers, &others);
+        for character in query.iter_mut() {
+            if character.0.client_id() == *client_id {
+                move_character(character, room, room_layers, position);
+            }
+        }
+        info!("client: {client_id:?} moved to room: {room:?}");
+    }
+}
+
+fn destroy_room_system(
+    mut commands: Commands,
+    mut rooms: ResMut<Rooms>,
+    mut events: EventReader<DestroyRoom>,
+    entities: Query<(Entity, &RoomId), Without<NetworkId>>,
+    mut characters: Query<RoomCharacter>,
+    mut selector: SpawnSelector
+) {
+    for DestroyRoom(room) in events.read() {
+        if *room == DEFAULT_ROOM || !rooms.contains(*room) {
+            warn!("room: {room:?} can not be destroyed");
+            continue;
+        }
+
+        for (e, room_id) in entities.iter() {
+            if room_id == room {
+                commands.entity(e).despawn_recursive();
+            }
+        }
+
+        let members = rooms.members(*room).collect::<Vec<_>>();
+        for client_id in members.iter() {
+            rooms.join(*client_id, DEFAULT_ROOM);
+        }
+        rooms.layers.remove(room);
+
+        let Some(default_layers) = rooms.layers(DEFAULT_ROOM) else {
+            continue;
+        };
+        // members are placed one by one, so they do not share a point
+        let mut others = room_positions(&characters, DEFAULT_ROOM);
+        for character in characters.iter_mut() {
+            if members.contains(&character.0.client_id()) {
+                let position = selector.select(DEFAULT_ROOM, default_layers, &others);
+                others.push(position);
+                move_character(character, DEFAULT_ROOM, default_layers, position);
+            }
+        }
+        info!("room: {room:?} destroyed, {} members moved to default room", members.len());
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn room_visibility_system(
+    mut connected_clients: ResMut<ConnectedClients>,
+    mut rooms: ResMut<Rooms>,
+    changed: Query<(Entity, &RoomId), (With<Replicated>, Changed<RoomId>)>,
+    all: Query<(Entity, &RoomId), With<Replicated>>
+) {
+    let dirty = std::mem::take(&mut rooms.dirty);
+    if matches!(connected_clients.visibility_policy(), VisibilityPolicy::All) {
+        return;
+    }
+
+    for client in connected_clients.iter_mut() {
+        let Some(room) = rooms.room_of(client.id()) else {
+            continue;
+        };
+        let full_update = dirty.contains(&client.id());
+        let visibility = client.visibility_mut();
+        if full_update {
+            for (e, room_id) in all.iter() {
+                visibility.set_visibility(e, *room_id == room);
+            }
+        } else {
+            for (e, room_id) in changed.iter() {
+                visibility.set_visibility(e, *room_id == room);
+            }
+        }
+    }
+}
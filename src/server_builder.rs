@@ -33,7 +33,8 @@ impl ServerBuilder {
         .set(
             ServerPlugin{
                 tick_policy: TickPolicy::MaxTickRate(self.network_tick_rate),
-                //visibility_policy: VisibilityPolicy::Whitelist,
+                // entities are made visible to members of their room
+                visibility_policy: VisibilityPolicy::Whitelist,
                 ..default()
             }
         );
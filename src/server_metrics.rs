@@ -0,0 +1,424 @@
+// server health metrics
+// tick and physics step timing, entity counts, per client network info
+// and bytes per replicon channel, counted between replicon sets
+// exported as prometheus text on a local http port and logged periodically
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, Instant}
+};
+use bevy::{
+    prelude::*,
+    ecs::entity::Entities
+};
+use bevy_replicon::{
+    prelude::*,
+    core::channels::ReplicationChannel
+};
+use bevy_replicon_renet::renet::RenetServer;
+use bevy_xpbd_3d::prelude::*;
+use crate::*;
+
+pub const DEFAULT_METRICS_LOG_INTERVAL: Duration = Duration::from_secs(30);
+const METRICS_RENDER_INTERVAL: Duration = Duration::from_secs(1);
+const METRICS_PREFIX: &str = "netcharacon";
+
+#[derive(Default, Clone, Copy)]
+pub struct DurationSummary {
+    pub count: u64,
+    pub sum: f64,
+    // since last log summary
+    pub window_max: f64
+}
+
+impl DurationSummary {
+    #[inline]
+    pub fn observe(&mut self, d: Duration) {
+        let secs = d.as_secs_f64();
+        self.count += 1;
+        self.sum += secs;
+        self.window_max = self.window_max.max(secs);
+    }
+
+    #[inline]
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct ChannelMetrics {
+    pub messages: u64,
+    pub bytes: u64
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct ClientMetrics {
+    pub rtt: f64,
+    pub packet_loss: f64,
+    pub bytes_sent_per_sec: f64,
+    pub bytes_received_per_sec: f64
+}
+
+#[derive(Resource, Default)]
+pub struct ServerMetrics {
+    pub fixed_update: DurationSummary,
+    pub physics_step: DurationSummary,
+    pub entities: u32,
+    pub characters: usize,
+    pub replicated: usize,
+    // keyed by channel id
+    pub sent: BTreeMap<u8, ChannelMetrics>,
+    pub received: BTreeMap<u8, ChannelMetrics>,
+    pub clients: BTreeMap<u64, ClientMetrics>
+}
+
+#[inline]
+fn server_channel_name(channel_id: u8) -> String {
+    if channel_id == u8::from(ReplicationChannel::Init) {
+        "replication_init".to_string()
+    } else if channel_id == u8::from(ReplicationChannel::Update) {
+        "replication_update".to_string()
+    } else {
+        format!("channel_{channel_id}")
+    }
+}
+
+impl ServerMetrics {
+    // bytes of replication messages, both init and update
+    pub fn replication_bytes(&self) -> u64 {
+        [ReplicationChannel::Init, ReplicationChannel::Update].into_iter()
+        .filter_map(|c| self.sent.get(&c.into()))
+        .map(|m| m.bytes)
+        .sum()
+    }
+
+    pub fn to_prometheus(&self) -> String {
+        let p = METRICS_PREFIX;
+        let mut s = String::new();
+
+        for (name, summary) in [
+            ("fixed_update", &self.fixed_update),
+            ("physics_step", &self.physics_step)
+        ] {
+            let _ = writeln!(s, "# TYPE {p}_{name}_seconds summary");
+            let _ = writeln!(s, "{p}_{name}_seconds_sum {}", summary.sum);
+            let _ = writeln!(s, "{p}_{name}_seconds_count {}", summary.count);
+        }
+
+        let _ = writeln!(s, "# TYPE {p}_entities gauge");
+        let _ = writeln!(s, "{p}_entities {}", self.entities);
+        let _ = writeln!(s, "# TYPE {p}_characters gauge");
+        let _ = writeln!(s, "{p}_characters {}", self.characters);
+        let _ = writeln!(s, "# TYPE {p}_replicated_entities gauge");
+        let _ = writeln!(s, "{p}_replicated_entities {}", self.replicated);
+
+        write_channel_counters(&mut s, "sent", &self.sent, server_channel_name);
+        write_channel_counters(&mut s, "received", &self.received, |id| format!("channel_{id}"));
+
+        write_client_gauges(&mut s, "rtt_seconds", &self.clients, |c| c.rtt / 1000.0);
+        write_client_gauges(&mut s, "packet_loss_ratio", &self.clients, |c| c.packet_loss);
+        write_client_gauges(&mut s, "client_sent_bytes_per_second", &self.clients, |c| c.bytes_sent_per_sec);
+        write_client_gauges(&mut s, "client_received_bytes_per_second", &self.clients, |c| c.bytes_received_per_sec);
+        s
+    }
+}
+
+fn write_channel_counters(
+    s: &mut String,
+    direction: &str,
+    channels: &BTreeMap<u8, ChannelMetrics>,
+    channel_name: impl Fn(u8) -> String
+) {
+    let p = METRICS_PREFIX;
+    let _ = writeln!(s, "# TYPE {p}_{direction}_bytes_total counter");
+    for (id, m) in channels.iter() {
+        let _ = writeln!(s, "{p}_{direction}_bytes_total{{channel=\"{}\"}} {}", channel_name(*id), m.bytes);
+    }
+    let _ = writeln!(s, "# TYPE {p}_{direction}_messages_total counter");
+    for (id, m) in channels.iter() {
+        let _ = writeln!(s, "{p}_{direction}_messages_total{{channel=\"{}\"}} {}", channel_name(*id), m.messages);
+    }
+}
+
+fn write_client_gauges(
+    s: &mut String,
+    name: &str,
+    clients: &BTreeMap<u64, ClientMetrics>,
+    value: impl Fn(&ClientMetrics) -> f64
+) {
+    let p = METRICS_PREFIX;
+    let _ = writeln!(s, "# TYPE {p}_{name} gauge");
+    for (client_id, c) in clients.iter() {
+        let _ = writeln!(s, "{p}_{name}{{client=\"{client_id}\"}} {}", value(c));
+    }
+}
+
+// rendered text shared with http thread
+#[derive(Resource, Clone, Default)]
+struct MetricsExport(Arc<RwLock<String>>);
+
+#[derive(Resource, Default)]
+struct MetricsClock {
+    fixed_start: Option<Instant>,
+    physics_start: Option<Instant>
+}
+
+pub struct ServerMetricsPlugin {
+    // keep this on loopback
+    pub listen_addr: Option<SocketAddr>,
+    pub log_interval: Duration
+}
+
+impl Default for ServerMetricsPlugin {
+    fn default() -> Self {
+        Self {
+            listen_addr: None,
+            log_interval: DEFAULT_METRICS_LOG_INTERVAL
+        }
+    }
+}
+
+impl Plugin for ServerMetricsPlugin {
+    fn build(&self, app: &mut App) {
+        let export = MetricsExport::default();
+        if let Some(addr) = self.listen_addr {
+            match TcpListener::bind(addr) {
+                Ok(listener) => {
+                    info!("metrics served at: http://{addr}/metrics");
+                    spawn_http_server(listener, export.clone());
+                }
+                Err(e) => error!("failed to bind metrics server: {e}")
+            }
+        }
+
+        app.init_resource::<ServerMetrics>()
+        .init_resource::<MetricsClock>()
+        .insert_resource(export)
+        .insert_resource(MetricsLogTimer(Timer::new(self.log_interval, TimerMode::Repeating)))
+        .add_systems(FixedFirst, begin_fixed_update)
+        .add_systems(FixedLast, end_fixed_update)
+        .add_systems(FixedUpdate, (
+            begin_physics_step
+            .after(PhysicsSet::Prepare)
+            .before(PhysicsSet::StepSimulation),
+            end_physics_step
+            .after(PhysicsSet::StepSimulation)
+            .before(PhysicsSet::Sync)
+        ))
+        .add_systems(PreUpdate,
+            count_received
+            .after(ServerSet::ReceivePackets)
+            .before(ServerSet::Receive)
+            .run_if(resource_exists::<RepliconServer>)
+        )
+        .add_systems(PostUpdate,
+            count_sent
+            .after(ServerSet::Send)
+            .before(ServerSet::SendPackets)
+            .run_if(resource_exists::<RepliconServer>)
+        )
+        .add_systems(Last, (
+            collect_metrics,
+            render_metrics,
+            log_metrics
+        ).chain());
+    }
+}
+
+#[derive(Resource)]
+struct MetricsLogTimer(Timer);
+
+fn begin_fixed_update(mut clock: ResMut<MetricsClock>) {
+    clock.fixed_start = Some(Instant::now());
+}
+
+fn end_fixed_update(
+    mut clock: ResMut<MetricsClock>,
+    mut metrics: ResMut<ServerMetrics>
+) {
+    if let Some(start) = clock.fixed_start.take() {
+        metrics.fixed_update.observe(start.elapsed());
+    }
+}
+
+fn begin_physics_step(mut clock: ResMut<MetricsClock>) {
+    clock.physics_start = Some(Instant::now());
+}
+
+fn end_physics_step(
+    mut clock: ResMut<MetricsClock>,
+    mut metrics: ResMut<ServerMetrics>
+) {
+    if let Some(start) = clock.physics_start.take() {
+        metrics.physics_step.observe(start.elapsed());
+    }
+}
+
+// messages are drained and put back in the same order
+fn count_received(
+    mut server: ResMut<RepliconServer>,
+    mut metrics: ResMut<ServerMetrics>,
+    channels: Res<RepliconChannels>
+) {
+    if !server.is_running() {
+        return;
+    }
+
+    for channel_id in 0..channels.client_channels().len() as u8 {
+        let messages = server.receive(channel_id).collect::<Vec<_>>();
+        let m = metrics.received.entry(channel_id).or_default();
+        for (client_id, message) in messages {
+            m.messages += 1;
+            m.bytes += message.len() as u64;
+            server.insert_received(client_id, channel_id, message);
+        }
+    }
+}
+
+fn count_sent(
+    mut server: ResMut<RepliconServer>,
+    mut metrics: ResMut<ServerMetrics>
+) {
+    if !server.is_running() {
+        return;
+    }
+
+    let messages = server.drain_sent().collect::<Vec<_>>();
+    for (client_id, channel_id, message) in messages {
+        let m = metrics.sent.entry(channel_id).or_default();
+        m.messages += 1;
+        m.bytes += message.len() as u64;
+        server.send(client_id, channel_id, message);
+    }
+}
+
+fn collect_metrics(
+    mut metrics: ResMut<ServerMetrics>,
+    entities: &Entities,
+    characters: Query<(), With<NetworkId>>,
+    replicated: Query<(), With<Replicated>>,
+    renet: Option<Res<RenetServer>>
+) {
+    metrics.entities = entities.len();
+    metrics.characters = characters.iter().len();
+    metrics.replicated = replicated.iter().len();
+
+    metrics.clients.clear();
+    let Some(renet) = renet else {
+        return;
+    };
+    for client_id in renet.clients_id_iter() {
+        let Ok(info) = renet.network_info(client_id) else {
+            continue;
+        };
+        metrics.clients.insert(client_id.raw(), ClientMetrics {
+            rtt: info.rtt,
+            packet_loss: info.packet_loss,
+            bytes_sent_per_sec: info.bytes_sent_per_second,
+            bytes_received_per_sec: info.bytes_received_per_second
+        });
+    }
+}
+
+fn render_metrics(
+    metrics: Res<ServerMetrics>,
+    export: Res<MetricsExport>,
+    time: Res<Time<Real>>,
+    mut last: Local<Option<Duration>>
+) {
+    let now = time.elapsed();
+    if last.is_some_and(|l| now - l < METRICS_RENDER_INTERVAL) {
+        return;
+    }
+    *last = Some(now);
+
+    if let Ok(mut text) = export.0.write() {
+        *text = metrics.to_prometheus();
+    }
+}
+
+fn log_metrics(
+    mut metrics: ResMut<ServerMetrics>,
+    mut timer: ResMut<MetricsLogTimer>,
+    time: Res<Time<Real>>
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let worst_rtt = metrics.clients.values()
+    .map(|c| c.rtt)
+    .fold(0.0, f64::max);
+    info!(
+        "metrics: fixed update mean: {:.3}ms max: {:.3}ms, physics mean: {:.3}ms max: {:.3}ms, \
+        entities: {}, characters: {}, replicated: {}, clients: {}, worst rtt: {worst_rtt:.1}ms, \
+        replication sent: {}bytes",
+        metrics.fixed_update.mean() * 1000.0,
+        metrics.fixed_update.window_max * 1000.0,
+        metrics.physics_step.mean() * 1000.0,
+        metrics.physics_step.window_max * 1000.0,
+        metrics.entities,
+        metrics.characters,
+        metrics.replicated,
+        metrics.clients.len(),
+        metrics.replication_bytes()
+    );
+    metrics.fixed_update.window_max = 0.0;
+    metrics.physics_step.window_max = 0.0;
+}
+
+fn spawn_http_server(listener: TcpListener, export: MetricsExport) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = serve_metrics(stream, &export) {
+                        warn!("failed to serve metrics: {e}");
+                    }
+                }
+                Err(e) => warn!("failed to accept metrics request: {e}")
+            }
+        }
+    });
+}
+
+// minimal http/1.0, one request per connection
+fn serve_metrics(mut stream: TcpStream, export: &MetricsExport) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // headers are not used
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+    let (status, body) = if request_line.starts_with("GET ") && (path == "/metrics" || path == "/") {
+        let body = export.0.read()
+        .map(|s| s.clone())
+        .unwrap_or_default();
+        ("200 OK", body)
+    } else {
+        ("404 Not Found", String::new())
+    };
+
+    write!(
+        stream,
+        "HTTP/1.0 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()?;
+    Ok(())
+}
@@ -0,0 +1,488 @@
+// records everything that drives server simulation
+// connections with user data, disconnections, room changes
+// and actions tagged by SimulationTick
+// replay feeds the record back without network
+// one FixedUpdate per App::update, so ticks line up with the recording
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+    time::Duration
+};
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use bevy_replicon::{
+    prelude::*,
+    bincode::{self, DefaultOptions, Options}
+};
+use serde::{Serialize, Deserialize};
+use crate::{
+    *,
+    config::PHYSICS_FIXED_TICK_RATE64,
+    network_character_controller::NetworkCharacterController,
+    room::*,
+    user_data::*,
+    projectile::FireProjectile,
+    interaction::InteractRequest,
+    game_server::{AdmissionSet, ClientAdmitted},
+    admin_console::{TeleportCharacter, SetTickRate, SetPaused}
+};
+
+const RECORD_MAGIC: [u8; 4] = *b"NCRC";
+const RECORD_VERSION: u16 = 6;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum RecordEntry {
+    Connected {
+        tick: u32,
+        client_id: ClientId,
+        user_data: Vec<u8>
+    },
+    Disconnected {
+        tick: u32,
+        client_id: ClientId
+    },
+    Action {
+        tick: u32,
+        client_id: ClientId,
+        action: NetworkAction
+    },
+    Fire {
+        tick: u32,
+        client_id: ClientId,
+        request: FireProjectile
+    },
+    Interact {
+        tick: u32,
+        client_id: ClientId
+    },
+    CreateRoom {
+        tick: u32,
+        room: RoomId
+    },
+    DestroyRoom {
+        tick: u32,
+        room: RoomId
+    },
+    MoveToRoom {
+        tick: u32,
+        client_id: ClientId,
+        room: RoomId
+    },
+    Teleport {
+        tick: u32,
+        client_id: ClientId,
+        position: Vec3
+    },
+    SetTickRate {
+        tick: u32,
+        hz: f64
+    },
+    SetPaused {
+        tick: u32,
+        paused: bool
+    }
+}
+
+impl RecordEntry {
+    #[inline]
+    pub fn tick(&self) -> u32 {
+        match self {
+            RecordEntry::Connected { tick, .. } => *tick,
+            RecordEntry::Disconnected { tick, .. } => *tick,
+            RecordEntry::Action { tick, .. } => *tick,
+            RecordEntry::Fire { tick, .. } => *tick,
+            RecordEntry::Interact { tick, .. } => *tick,
+            RecordEntry::CreateRoom { tick, .. } => *tick,
+            RecordEntry::DestroyRoom { tick, .. } => *tick,
+            RecordEntry::MoveToRoom { tick, .. } => *tick,
+            RecordEntry::Teleport { tick, .. } => *tick,
+            RecordEntry::SetTickRate { tick, .. } => *tick,
+            RecordEntry::SetPaused { tick, .. } => *tick
+        }
+    }
+}
+
+pub struct RecordWriter<W: Write> {
+    writer: W
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(mut writer: W) -> anyhow::Result<Self> {
+        writer.write_all(&RECORD_MAGIC)?;
+        writer.write_all(&RECORD_VERSION.to_le_bytes())?;
+        Ok(Self { writer })
+    }
+
+    #[inline]
+    pub fn write(&mut self, entry: &RecordEntry) -> anyhow::Result<()> {
+        DefaultOptions::new().serialize_into(&mut self.writer, entry)?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub fn read_record<R: Read>(mut reader: R) -> anyhow::Result<Vec<RecordEntry>> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != RECORD_MAGIC {
+        anyhow::bail!("not a server record");
+    }
+    let mut version = [0u8; 2];
+    reader.read_exact(&mut version)?;
+    let version = u16::from_le_bytes(version);
+    if version != RECORD_VERSION {
+        anyhow::bail!("unsupported record version: {version}");
+    }
+
+    let mut entries = Vec::new();
+    loop {
+        match DefaultOptions::new().deserialize_from(&mut reader) {
+            Ok(entry) => entries.push(entry),
+            // end of record, or the last entry was cut off by a killed server
+            Err(e) if matches!(
+                e.as_ref(),
+                bincode::ErrorKind::Io(io) if io.kind() == ErrorKind::UnexpectedEof
+            ) => break,
+            Err(e) => return Err(e.into())
+        }
+    }
+    Ok(entries)
+}
+
+#[derive(Resource)]
+pub struct ServerRecorder {
+    writer: RecordWriter<BufWriter<File>>
+}
+
+impl ServerRecorder {
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self {
+            writer: RecordWriter::new(BufWriter::new(file))?
+        })
+    }
+
+    #[inline]
+    fn write(&mut self, entry: RecordEntry) {
+        if let Err(e) = self.writer.write(&entry) {
+            error!("failed to write server record: {e}");
+        }
+    }
+}
+
+// insert ServerRecorder resource to start recording
+pub struct ServerRecorderPlugin;
+
+impl Plugin for ServerRecorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate,
+            record_server_event
+            .after(ServerSet::Receive)
+            .after(AdmissionSet)
+            .run_if(resource_exists::<ServerRecorder>)
+        )
+        .add_systems(FixedUpdate,
+            record_action
+            .before(BEFORE_PHYSICS_SET)
+            .run_if(resource_exists::<ServerRecorder>)
+        )
+        .add_systems(Last,
+            flush_record
+            .run_if(resource_exists::<ServerRecorder>)
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn record_server_event(
+    mut recorder: ResMut<ServerRecorder>,
+    mut events: EventReader<ServerEvent>,
+    mut admitted: EventReader<ClientAdmitted>,
+    mut create_rooms: EventReader<CreateRoom>,
+    mut destroy_rooms: EventReader<DestroyRoom>,
+    mut move_to_rooms: EventReader<MoveToRoom>,
+    mut teleports: EventReader<TeleportCharacter>,
+    mut tick_rates: EventReader<SetTickRate>,
+    mut pauses: EventReader<SetPaused>,
+    user_data: Res<ConnectionUserData>,
+    tick: Res<SimulationTick>
+) {
+    let tick = tick.get();
+
+    for CreateRoom(room) in create_rooms.read() {
+        recorder.write(RecordEntry::CreateRoom { tick, room: *room });
+    }
+    for DestroyRoom(room) in destroy_rooms.read() {
+        recorder.write(RecordEntry::DestroyRoom { tick, room: *room });
+    }
+    for MoveToRoom { client_id, room } in move_to_rooms.read() {
+        recorder.write(RecordEntry::MoveToRoom {
+            tick,
+            client_id: *client_id,
+            room: *room
+        });
+    }
+    for TeleportCharacter { client_id, position } in teleports.read() {
+        recorder.write(RecordEntry::Teleport {
+            tick,
+            client_id: *client_id,
+            position: *position
+        });
+    }
+    for SetTickRate(hz) in tick_rates.read() {
+        recorder.write(RecordEntry::SetTickRate { tick, hz: *hz });
+    }
+    for SetPaused(paused) in pauses.read() {
+        recorder.write(RecordEntry::SetPaused { tick, paused: *paused });
+    }
+
+    // recorded when the character is spawned, replay has no authentication
+    // or bans and spawns it in the same tick as the connection
+    for ClientAdmitted { client_id, .. } in admitted.read() {
+        recorder.write(RecordEntry::Connected {
+            tick,
+            client_id: *client_id,
+            user_data: user_data.get(*client_id)
+                .map(|u| u.as_bytes().to_vec())
+                .unwrap_or_default()
+        });
+    }
+    for e in events.read() {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = e {
+            recorder.write(RecordEntry::Disconnected {
+                tick,
+                client_id: *client_id
+            });
+        }
+    }
+}
+
+fn record_action(
+    mut recorder: ResMut<ServerRecorder>,
+    mut actions: EventReader<FromClient<NetworkAction>>,
+    mut fires: EventReader<FromClient<FireProjectile>>,
+    mut interacts: EventReader<FromClient<InteractRequest>>,
+    tick: Res<SimulationTick>
+) {
+    for FromClient { client_id, event: action } in actions.read() {
+        recorder.write(RecordEntry::Action {
+            tick: tick.get(),
+            client_id: *client_id,
+            action: action.clone()
+        });
+    }
+    for FromClient { client_id, event: request } in fires.read() {
+        recorder.write(RecordEntry::Fire {
+            tick: tick.get(),
+            client_id: *client_id,
+            request: *request
+        });
+    }
+    for FromClient { client_id, .. } in interacts.read() {
+        recorder.write(RecordEntry::Interact {
+            tick: tick.get(),
+            client_id: *client_id
+        });
+    }
+}
+
+fn flush_record(mut recorder: ResMut<ServerRecorder>) {
+    if let Err(e) = recorder.writer.flush() {
+        error!("failed to flush server record: {e}");
+    }
+}
+
+#[derive(Resource)]
+pub struct ServerReplay {
+    entries: VecDeque<RecordEntry>,
+    last_tick: u32
+}
+
+impl ServerReplay {
+    pub fn new(entries: Vec<RecordEntry>) -> Self {
+        let last_tick = entries.iter()
+        .map(RecordEntry::tick)
+        .max()
+        .unwrap_or_default();
+
+        Self {
+            entries: entries.into(),
+            last_tick
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self::new(read_record(BufReader::new(file))?))
+    }
+
+    #[inline]
+    pub fn last_tick(&self) -> u32 {
+        self.last_tick
+    }
+
+    #[inline]
+    pub fn is_finished(&self, tick: &SimulationTick) -> bool {
+        self.entries.is_empty() && tick.get() > self.last_tick
+    }
+}
+
+#[inline]
+pub fn replay_time_step() -> Duration {
+    Duration::from_secs_f64(1.0 / PHYSICS_FIXED_TICK_RATE64)
+}
+
+// insert ServerReplay resource to feed events
+// server must not be running
+pub struct ServerReplayPlugin;
+
+impl Plugin for ServerReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(replay_time_step()))
+        .init_resource::<Trajectories>()
+        .add_systems(PreUpdate,
+            feed_replay
+            .in_set(ServerSet::SendEvents)
+            .run_if(resource_exists::<ServerReplay>)
+        )
+        .add_systems(Last, (
+            collect_trajectories,
+            follow_fixed_time_step,
+            resume_after_replay
+        ));
+    }
+}
+
+// keeps one FixedUpdate per App::update after a recorded tick rate change
+fn follow_fixed_time_step(
+    fixed_time: Res<Time<Fixed>>,
+    mut strategy: ResMut<TimeUpdateStrategy>
+) {
+    let step = fixed_time.timestep();
+    if !matches!(*strategy, TimeUpdateStrategy::ManualDuration(d) if d == step) {
+        *strategy = TimeUpdateStrategy::ManualDuration(step);
+    }
+}
+
+// no tick passes while paused, a record that ends paused would never finish
+fn resume_after_replay(
+    replay: Option<Res<ServerReplay>>,
+    mut virtual_time: ResMut<Time<Virtual>>
+) {
+    if replay.is_some_and(|r| r.entries.is_empty()) && virtual_time.is_paused() {
+        virtual_time.unpause();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn feed_replay(
+    mut replay: ResMut<ServerReplay>,
+    mut server_events: EventWriter<ServerEvent>,
+    mut actions: EventWriter<FromClient<NetworkAction>>,
+    mut fires: EventWriter<FromClient<FireProjectile>>,
+    mut interacts: EventWriter<FromClient<InteractRequest>>,
+    mut create_rooms: EventWriter<CreateRoom>,
+    mut destroy_rooms: EventWriter<DestroyRoom>,
+    mut move_to_rooms: EventWriter<MoveToRoom>,
+    mut teleports: EventWriter<TeleportCharacter>,
+    mut tick_rates: EventWriter<SetTickRate>,
+    mut pauses: EventWriter<SetPaused>,
+    mut user_data: ResMut<ConnectionUserData>,
+    tick: Res<SimulationTick>
+) {
+    while replay.entries.front()
+    .is_some_and(|entry| entry.tick() <= tick.get()) {
+        let Some(entry) = replay.entries.pop_front() else {
+            break;
+        };
+
+        match entry {
+            RecordEntry::Connected { client_id, user_data: bytes, .. } => {
+                if !bytes.is_empty() {
+                    user_data.insert(client_id, UserData::from_slice(&bytes));
+                }
+                server_events.send(ServerEvent::ClientConnected { client_id });
+            }
+            RecordEntry::Disconnected { client_id, .. } => {
+                server_events.send(ServerEvent::ClientDisconnected {
+                    client_id,
+                    reason: "replay".to_string()
+                });
+            }
+            RecordEntry::Action { client_id, action, .. } => {
+                actions.send(FromClient { client_id, event: action });
+            }
+            RecordEntry::Fire { client_id, request, .. } => {
+                fires.send(FromClient { client_id, event: request });
+            }
+            RecordEntry::Interact { client_id, .. } => {
+                interacts.send(FromClient { client_id, event: InteractRequest });
+            }
+            RecordEntry::CreateRoom { room, .. } => {
+                create_rooms.send(CreateRoom(room));
+            }
+            RecordEntry::DestroyRoom { room, .. } => {
+                destroy_rooms.send(DestroyRoom(room));
+            }
+            RecordEntry::MoveToRoom { client_id, room, .. } => {
+                move_to_rooms.send(MoveToRoom { client_id, room });
+            }
+            RecordEntry::Teleport { client_id, position, .. } => {
+                teleports.send(TeleportCharacter { client_id, position });
+            }
+            RecordEntry::SetTickRate { hz, .. } => {
+                tick_rates.send(SetTickRate(hz));
+            }
+            RecordEntry::SetPaused { paused, .. } => {
+                pauses.send(SetPaused(paused));
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TrajectorySample {
+    pub tick: u32,
+    pub translation: Vec3,
+    pub yaw: f32
+}
+
+impl TrajectorySample {
+    // for comparing bit identical output
+    #[inline]
+    pub fn to_bits(&self) -> [u32; 5] {
+        [
+            self.tick,
+            self.translation.x.to_bits(),
+            self.translation.y.to_bits(),
+            self.translation.z.to_bits(),
+            self.yaw.to_bits()
+        ]
+    }
+}
+
+#[derive(Resource, Default, Deref)]
+pub struct Trajectories(BTreeMap<ClientId, Vec<TrajectorySample>>);
+
+pub fn collect_trajectories(
+    query: Query<(&NetworkId, &NetworkCharacterController)>,
+    mut trajectories: ResMut<Trajectories>,
+    tick: Res<SimulationTick>
+) {
+    for (net_id, net_cc) in query.iter() {
+        trajectories.0.entry(net_id.client_id())
+        .or_default()
+        .push(TrajectorySample {
+            tick: tick.get(),
+            translation: net_cc.translation,
+            yaw: net_cc.yaw
+        });
+    }
+}
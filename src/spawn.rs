@@ -0,0 +1,227 @@
+// spawn points and out of bounds respawn
+// points and kill bounds are spawned with each level, so they belong to a room
+// selection runs on simulation state only, replay picks the same points
+
+use bevy::{
+    prelude::*,
+    ecs::system::SystemParam
+};
+use bevy_replicon::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::{
+    *,
+    room::{RoomId, Rooms},
+    character_controller::FallTracker,
+    health::Dead
+};
+
+// characters closer than this count as crowd for LeastOccupied
+pub const SPAWN_CROWD_RADIUS: f32 = 10.0;
+// other characters closer than this make a point occupied
+pub const SPAWN_CLEARANCE: f32 = CHARACTER_RADIUS * 2.0 + 0.5;
+pub const SPAWN_RNG_SEED: u64 = 0x5eed;
+
+#[derive(Component, Default)]
+pub struct SpawnPoint;
+
+// characters leaving this box respawn
+#[derive(Component, Clone, Copy)]
+pub struct KillBounds {
+    pub min: Vec3,
+    pub max: Vec3
+}
+
+impl KillBounds {
+    #[inline]
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SpawnStrategy {
+    Random,
+    #[default]
+    LeastOccupied,
+    FarthestFromOthers
+}
+
+#[derive(Resource, Clone, Copy)]
+pub struct SpawnConfig {
+    pub strategy: SpawnStrategy,
+    pub crowd_radius: f32
+}
+
+impl Default for SpawnConfig {
+    fn default() -> Self {
+        Self {
+            strategy: SpawnStrategy::default(),
+            crowd_radius: SPAWN_CROWD_RADIUS
+        }
+    }
+}
+
+// seeded, so Random strategy is reproduced in replay
+#[derive(Resource)]
+pub struct SpawnRng(fastrand::Rng);
+
+impl Default for SpawnRng {
+    fn default() -> Self {
+        Self(fastrand::Rng::with_seed(SPAWN_RNG_SEED))
+    }
+}
+
+// server to clients, predicted local character snaps to position
+#[derive(Event, Serialize, Deserialize, Clone)]
+pub struct CharacterRespawned {
+    pub client_id: ClientId,
+    pub position: Vec3
+}
+
+#[allow(clippy::type_complexity)]
+#[derive(SystemParam)]
+pub struct SpawnSelector<'w, 's> {
+    points: Query<'w, 's, (&'static Transform, &'static RoomId), (With<SpawnPoint>, Without<NetworkId>)>,
+    // pipeline only, SpatialQuery would conflict with callers moving characters
+    spatial_query: Res<'w, SpatialQueryPipeline>,
+    config: Res<'w, SpawnConfig>,
+    rng: ResMut<'w, SpawnRng>
+}
+
+impl<'w, 's> SpawnSelector<'w, 's> {
+    // others are positions of other characters in the room,
+    // including ones spawned in the same frame
+    pub fn select(&mut self, room: RoomId, layers: CollisionLayers, others: &[Vec3]) -> Vec3 {
+        let mut candidates = self.points.iter()
+        .filter(|(_, r)| **r == room)
+        .map(|(t, _)| t.translation)
+        .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return CHARACTER_SPAWN_POSITION;
+        }
+        // query order is not part of simulation state
+        candidates.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.z.total_cmp(&b.z)));
+
+        let shape = Collider::capsule(CHARACTER_HIGHT, CHARACTER_RADIUS);
+        let filter = SpatialQueryFilter::from_mask(layers.filters);
+        let free = candidates.iter()
+        .copied()
+        .filter(|p| others.iter().all(|o| o.distance(*p) > SPAWN_CLEARANCE))
+        .filter(|p| self.spatial_query
+            .shape_intersections(&shape, *p, Quat::IDENTITY, filter.clone())
+            .is_empty()
+        )
+        .collect::<Vec<_>>();
+        let pool = if free.is_empty() {
+            warn!("all spawn points in room: {room:?} are occupied");
+            candidates
+        } else {
+            free
+        };
+
+        match self.config.strategy {
+            SpawnStrategy::Random => pool[self.rng.0.usize(..pool.len())],
+            SpawnStrategy::LeastOccupied => {
+                let radius = self.config.crowd_radius;
+                pool.iter()
+                .copied()
+                .min_by_key(|p| others.iter()
+                    .filter(|o| o.distance(*p) <= radius)
+                    .count()
+                )
+                .unwrap_or(CHARACTER_SPAWN_POSITION)
+            }
+            SpawnStrategy::FarthestFromOthers => {
+                let nearest = |p: Vec3| others.iter()
+                .map(|o| o.distance(p))
+                .fold(f32::INFINITY, f32::min);
+                pool.iter()
+                .copied()
+                .max_by(|a, b| nearest(*a).total_cmp(&nearest(*b)))
+                .unwrap_or(CHARACTER_SPAWN_POSITION)
+            }
+        }
+    }
+
+    // placed are all characters with their rooms, e itself is skipped
+    // None if the room no longer exists
+    pub fn select_respawn(
+        &mut self,
+        rooms: &Rooms,
+        e: Entity,
+        room: RoomId,
+        placed: &[(Entity, RoomId, Vec3)]
+    ) -> Option<Vec3> {
+        let layers = rooms.layers(room)?;
+        let others = placed.iter()
+        .filter(|(other, r, _)| *other != e && *r == room)
+        .map(|(.., p)| *p)
+        .collect::<Vec<_>>();
+        Some(self.select(room, layers, &others))
+    }
+}
+
+pub struct SpawnPlugin;
+
+impl Plugin for SpawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnConfig>()
+        .init_resource::<SpawnRng>()
+        .add_systems(FixedUpdate,
+            kill_bounds_system
+            .before(BEFORE_PHYSICS_SET)
+        );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn kill_bounds_system(
+    mut characters: Query<(
+        Entity,
+        &NetworkId,
+        &RoomId,
+        &mut Position,
+        &mut LinearVelocity,
+        &mut FallTracker
+    ), Without<Dead>>,
+    bounds: Query<(&KillBounds, &RoomId)>,
+    rooms: Res<Rooms>,
+    mut selector: SpawnSelector,
+    mut respawned: EventWriter<ToClients<CharacterRespawned>>
+) {
+    let snapshot = characters.iter()
+    .map(|(e, _, room, pos, ..)| (e, *room, pos.0))
+    .collect::<Vec<_>>();
+    let out = snapshot.iter()
+    .filter(|(_, room, pos)| bounds.iter()
+        .any(|(b, r)| r == room && !b.contains(*pos))
+    )
+    .map(|(e, room, _)| (*e, *room))
+    .collect::<Vec<_>>();
+
+    for (e, room) in out {
+        let Some(position) = selector.select_respawn(&rooms, e, room, &snapshot) else {
+            continue;
+        };
+
+        let Ok((_, net_id, _, mut pos, mut vel, mut fall)) = characters.get_mut(e) else {
+            continue;
+        };
+        // Transform follows after the step,
+        // physics adds a written Transform on top of Position
+        pos.0 = position;
+        vel.0 = Vec3::ZERO;
+        fall.reset();
+
+        // clients in other rooms do not see the character
+        let client_id = net_id.client_id();
+        for member in rooms.members(room) {
+            respawned.send(ToClients {
+                mode: SendMode::Direct(member),
+                event: CharacterRespawned { client_id, position }
+            });
+        }
+        info!("client: {client_id:?} out of bounds, respawned at: {position}");
+    }
+}
@@ -0,0 +1,110 @@
+// netcode user data captured at connection
+// replay has no transport and fills this directly
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, Uuid}
+};
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::renet::{
+    self,
+    transport::{NetcodeServerTransport, NETCODE_USER_DATA_BYTES}
+};
+use crate::config::{USER_DATA_ROOM_ID, USER_DATA_SESSION_ID};
+
+#[derive(Clone, Copy)]
+pub struct UserData([u8; NETCODE_USER_DATA_BYTES]);
+
+impl UserData {
+    #[inline]
+    pub fn new(bytes: [u8; NETCODE_USER_DATA_BYTES]) -> Self {
+        Self(bytes)
+    }
+
+    // shorter input is zero padded
+    #[inline]
+    pub fn from_slice(slice: &[u8]) -> Self {
+        let mut bytes = [0u8; NETCODE_USER_DATA_BYTES];
+        let len = slice.len().min(NETCODE_USER_DATA_BYTES);
+        bytes[..len].copy_from_slice(&slice[..len]);
+        Self(bytes)
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8; NETCODE_USER_DATA_BYTES] {
+        &self.0
+    }
+
+    #[inline]
+    pub fn session_id(&self) -> Uuid {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&self.0[USER_DATA_SESSION_ID]);
+        Uuid::from_bytes(bytes)
+    }
+
+    #[inline]
+    pub fn room_id(&self) -> u32 {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&self.0[USER_DATA_ROOM_ID]);
+        u32::from_le_bytes(bytes)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ConnectionUserData(HashMap<ClientId, UserData>);
+
+impl ConnectionUserData {
+    #[inline]
+    pub fn get(&self, client_id: ClientId) -> Option<&UserData> {
+        self.0.get(&client_id)
+    }
+
+    #[inline]
+    pub fn insert(&mut self, client_id: ClientId, user_data: UserData) {
+        self.0.insert(client_id, user_data);
+    }
+}
+
+pub struct ConnectionUserDataPlugin;
+
+impl Plugin for ConnectionUserDataPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConnectionUserData>()
+        .add_systems(PreUpdate,
+            capture_user_data
+            .in_set(ServerSet::Receive)
+        )
+        // kept until the end of frame for disconnection handlers
+        .add_systems(PostUpdate, remove_user_data);
+    }
+}
+
+fn capture_user_data(
+    mut events: EventReader<ServerEvent>,
+    mut user_data: ResMut<ConnectionUserData>,
+    transport: Option<Res<NetcodeServerTransport>>
+) {
+    let Some(transport) = transport else {
+        return;
+    };
+
+    for e in events.read() {
+        if let ServerEvent::ClientConnected { client_id } = e {
+            let renet_id = renet::ClientId::from_raw(client_id.get());
+            if let Some(bytes) = transport.user_data(renet_id) {
+                user_data.insert(*client_id, UserData::new(bytes));
+            }
+        }
+    }
+}
+
+fn remove_user_data(
+    mut events: EventReader<ServerEvent>,
+    mut user_data: ResMut<ConnectionUserData>
+) {
+    for e in events.read() {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = e {
+            user_data.0.remove(client_id);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use bevy_netcharacon_dev::{
+    *,
+    character_controller::*,
+    level::floor_collider
+};
+use common::*;
+
+const SETTLE_TICKS: u32 = 32;
+// capsules start this far apart, overlapping by 2 * radius - this
+const START_DISTANCE: f32 = 0.4;
+const TOLERANCE: f32 = 0.05;
+
+fn floor_app() -> App {
+    physics_app(|world| {
+        world.spawn((
+            TransformBundle::from_transform(
+                Transform::from_xyz(0.0, -0.5, 0.0)
+            ),
+            floor_collider()
+        ));
+    })
+}
+
+fn spawn(app: &mut App, x: f32, collision: CharacterCollision) -> Entity {
+    spawn_character_with(
+        app,
+        Transform::from_xyz(x, CHARACTER_SPAWN_POSITION.y, 0.0),
+        character_bundle().with_character_collision(collision)
+    ).id()
+}
+
+// x of both characters after they settled
+fn settle(
+    collision_1: CharacterCollision,
+    collision_2: CharacterCollision
+) -> (f32, f32) {
+    let mut app = floor_app();
+    let e1 = spawn(&mut app, -START_DISTANCE * 0.5, collision_1);
+    let e2 = spawn(&mut app, START_DISTANCE * 0.5, collision_2);
+    for _ in 0..SETTLE_TICKS {
+        app.update();
+    }
+    let x1 = app.world.get::<Position>(e1).unwrap().x;
+    let x2 = app.world.get::<Position>(e2).unwrap().x;
+    (x1, x2)
+}
+
+#[test]
+fn overlapping_characters_separate_symmetrically() {
+    let (x1, x2) = settle(CharacterCollision::default(), CharacterCollision::default());
+
+    assert!(
+        x2 - x1 >= CHARACTER_RADIUS * 2.0 - TOLERANCE,
+        "still overlapping: {x1} {x2}"
+    );
+    // equal mass, both moved the same distance
+    assert!((x1 + x2).abs() < TOLERANCE, "uneven split: {x1} {x2}");
+}
+
+#[test]
+fn mass_splits_separation() {
+    let heavy = CharacterCollision {
+        mass: 4.0,
+        ..default()
+    };
+    let (x1, x2) = settle(heavy, CharacterCollision::default());
+
+    assert!(x2 - x1 >= CHARACTER_RADIUS * 2.0 - TOLERANCE);
+    let moved_1 = (x1 + START_DISTANCE * 0.5).abs();
+    let moved_2 = (x2 - START_DISTANCE * 0.5).abs();
+    assert!(moved_1 < moved_2, "heavy moved more: {moved_1} {moved_2}");
+}
+
+#[test]
+fn higher_priority_is_not_pushed() {
+    let high = CharacterCollision {
+        priority: 1,
+        ..default()
+    };
+    let (x1, x2) = settle(high, CharacterCollision::default());
+
+    assert!(x2 - x1 >= CHARACTER_RADIUS * 2.0 - TOLERANCE);
+    assert!((x1 + START_DISTANCE * 0.5).abs() < TOLERANCE, "high priority moved: {x1}");
+}
+
+#[test]
+fn soft_push_separates_over_time() {
+    let soft = CharacterCollision {
+        stiffness: 0.05,
+        ..default()
+    };
+    let (x1, x2) = settle(soft, CharacterCollision::default());
+
+    assert!(x2 - x1 > START_DISTANCE, "soft push did nothing: {x1} {x2}");
+    assert!((x1 + x2).abs() < TOLERANCE);
+}
+
+#[test]
+fn disabled_collision_passes_through() {
+    let ghost = CharacterCollision {
+        enabled: false,
+        ..default()
+    };
+    let (x1, x2) = settle(ghost, CharacterCollision::default());
+
+    assert!((x2 - x1 - START_DISTANCE).abs() < TOLERANCE, "ghost was pushed: {x1} {x2}");
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use bevy_netcharacon_dev::{
+    *,
+    character_controller::*
+};
+use common::*;
+
+const FLOOR_LENGTH: f32 = 20.0;
+const WALL_THICKNESS: f32 = 1.0;
+const WALL_HEIGHT: f32 = 4.0;
+// inner faces of the walls
+const WALL_X: f32 = 1.0;
+const WALL_Z: f32 = -1.0;
+const WALK_TICKS: u32 = 90;
+const TOLERANCE: f32 = 0.02;
+
+// floor top is at y = 0, a wall faces -x at WALL_X,
+// with a corner another one faces +z at WALL_Z
+fn wall_app(corner: bool) -> App {
+    physics_app(|world| {
+        spawn_box(
+            world,
+            Transform::from_xyz(0.0, -0.5, 0.0),
+            Vec3::new(FLOOR_LENGTH, 1.0, FLOOR_LENGTH)
+        );
+        spawn_box(
+            world,
+            Transform::from_xyz(WALL_X + WALL_THICKNESS * 0.5, WALL_HEIGHT * 0.5, 0.0),
+            Vec3::new(WALL_THICKNESS, WALL_HEIGHT, FLOOR_LENGTH)
+        );
+        if corner {
+            spawn_box(
+                world,
+                Transform::from_xyz(0.0, WALL_HEIGHT * 0.5, WALL_Z - WALL_THICKNESS * 0.5),
+                Vec3::new(FLOOR_LENGTH, WALL_HEIGHT, WALL_THICKNESS)
+            );
+        }
+    })
+}
+
+fn spawn_on_floor(app: &mut App) -> Entity {
+    let e = spawn_character_with(
+        app,
+        standing_at(-1.0, 0.0, 1.0),
+        character_bundle().with_movement_solver(MovementSolver::CollideAndSlide)
+    ).id();
+    wait_for::<Grounded>(app, e);
+    e
+}
+
+// towards +x and -z, into the wall and the corner
+fn walk_diagonally(app: &mut App, e: Entity, mut each: impl FnMut(Vec3)) {
+    for _ in 0..WALK_TICKS {
+        send_action(app, e, ControllerAction::Move(Vec2::ONE.normalize()));
+        app.update();
+        each(position(app, e));
+    }
+}
+
+#[test]
+fn slides_along_wall() {
+    let mut app = wall_app(false);
+    let e = spawn_on_floor(&mut app);
+    let start = position(&app, e);
+    walk_diagonally(&mut app, e, |pos| {
+        assert!(pos.x <= WALL_X - CHARACTER_RADIUS + TOLERANCE, "in the wall at {pos}");
+        assert!((pos.y - start.y).abs() < TOLERANCE, "left the floor at {pos}");
+    });
+    let end = position(&app, e);
+    assert!((end.x - (WALL_X - CHARACTER_RADIUS)).abs() < 0.05, "end: {end}");
+    // velocity into the wall is removed, the rest keeps sliding
+    assert!(start.z - end.z > 3.0, "start: {start}, end: {end}");
+    let vel = app.world.get::<LinearVelocity>(e).unwrap();
+    assert!(vel.x.abs() < TOLERANCE, "velocity: {}", vel.0);
+    assert!(vel.z < -1.0, "velocity: {}", vel.0);
+}
+
+#[test]
+fn stops_in_crease() {
+    let mut app = wall_app(true);
+    let e = spawn_on_floor(&mut app);
+    let start = position(&app, e);
+    walk_diagonally(&mut app, e, |pos| {
+        assert!(pos.x <= WALL_X - CHARACTER_RADIUS + TOLERANCE, "in the wall at {pos}");
+        assert!(pos.z >= WALL_Z + CHARACTER_RADIUS - TOLERANCE, "in the wall at {pos}");
+        assert!((pos.y - start.y).abs() < TOLERANCE, "left the floor at {pos}");
+    });
+    let end = position(&app, e);
+    let corner = Vec3::new(WALL_X - CHARACTER_RADIUS, start.y, WALL_Z + CHARACTER_RADIUS);
+    assert!(end.distance(corner) < 0.05, "end: {end}, corner: {corner}");
+    // floor and both walls hold it, it does not creep or climb
+    let mut last = end;
+    walk_diagonally(&mut app, e, |pos| {
+        assert!(pos.distance(last) < 0.005, "moved from {last} to {pos}");
+        last = pos;
+    });
+}
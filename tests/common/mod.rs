@@ -0,0 +1,102 @@
+// fixtures shared by the simulation tests
+// each test binary uses only some of them
+#![allow(dead_code)]
+
+use bevy::{
+    prelude::*,
+    ecs::world::EntityWorldMut,
+    time::TimeUpdateStrategy
+};
+use bevy_replicon::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use bevy_netcharacon_dev::{
+    *,
+    character_controller::*,
+    instant_event_buffer::InstantEventBuffer,
+    server_recorder::replay_time_step
+};
+
+// every update runs one fixed tick, like the replay
+pub const SETTLE_TICKS: u32 = 8;
+
+// server side simulation without networking, plugins can still be added
+pub fn game_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+    .add_plugins(RepliconPlugins.build().disable::<ClientPlugin>())
+    .add_plugins(GameCommonPlugin)
+    .insert_resource(TimeUpdateStrategy::ManualDuration(replay_time_step()));
+    app
+}
+
+// level spawns the scenario geometry
+pub fn physics_app(level: impl FnOnce(&mut World)) -> App {
+    let mut app = game_app();
+    level(&mut app.world);
+    app.finish();
+    app.cleanup();
+    app
+}
+
+// static box by its center
+pub fn spawn_box(world: &mut World, transform: Transform, size: Vec3) -> Entity {
+    world.spawn((
+        TransformBundle::from_transform(transform),
+        RigidBody::Static,
+        Collider::cuboid(size.x, size.y, size.z)
+    )).id()
+}
+
+#[inline]
+pub fn character_bundle() -> CharacterControllerBundle {
+    CharacterControllerBundle::new(
+        Collider::capsule(CHARACTER_HIGHT, CHARACTER_RADIUS),
+        GRAVITY
+    )
+}
+
+pub fn spawn_character(app: &mut App, transform: Transform) -> EntityWorldMut<'_> {
+    spawn_character_with(app, transform, character_bundle())
+}
+
+pub fn spawn_character_with(
+    app: &mut App,
+    transform: Transform,
+    bundle: CharacterControllerBundle
+) -> EntityWorldMut<'_> {
+    app.world.spawn((
+        TransformBundle::from_transform(transform),
+        LockedAxes::new().lock_rotation_x().lock_rotation_z(),
+        bundle
+    ))
+}
+
+// center of a character standing on y
+#[inline]
+pub fn standing_at(x: f32, y: f32, z: f32) -> Transform {
+    Transform::from_xyz(x, y + CHARACTER_HIGHT * 0.5 + CHARACTER_RADIUS + 0.01, z)
+}
+
+// read in the next fixed tick
+pub fn send_action(app: &mut App, e: Entity, action: ControllerAction) {
+    let tick = app.world.resource::<SimulationTick>().get();
+    app.world.get_mut::<InstantEventBuffer<ControllerAction>>(e)
+    .unwrap()
+    .send(tick, action);
+}
+
+#[inline]
+pub fn position(app: &App, e: Entity) -> Vec3 {
+    app.world.get::<Transform>(e).unwrap().translation
+}
+
+// movement state components need a completed step
+pub fn wait_for<C: Component>(app: &mut App, e: Entity) {
+    for _ in 0..SETTLE_TICKS {
+        app.update();
+        if app.world.get::<C>(e).is_some() {
+            return;
+        }
+    }
+    panic!("{} was not added", std::any::type_name::<C>());
+}
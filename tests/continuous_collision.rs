@@ -0,0 +1,63 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use bevy_netcharacon_dev::{
+    *,
+    character_controller::*,
+    level::floor_collider
+};
+use common::*;
+
+const DROP_HEIGHT: f32 = 1000.0;
+// more than a capsule plus floor height per substep, so overlaps are skipped
+const DROP_SPEED: f32 = 5000.0;
+const DROP_TICKS: u32 = 64;
+
+// y of the character after the drop
+fn drop(solver: MovementSolver, ccd: ContinuousCollision) -> f32 {
+    // top at y = 0
+    let mut app = physics_app(|world| {
+        world.spawn((
+            TransformBundle::from_transform(
+                Transform::from_xyz(0.0, -0.5, 0.0)
+            ),
+            floor_collider()
+        ));
+    });
+    let e = spawn_character_with(
+        &mut app,
+        Transform::from_xyz(0.0, DROP_HEIGHT, 0.0),
+        character_bundle()
+        .with_movement_solver(solver)
+        .with_continuous_collision(ccd)
+    )
+    .insert(LinearVelocity(Vec3::NEG_Y * DROP_SPEED))
+    .id();
+    for _ in 0..DROP_TICKS {
+        app.update();
+    }
+    position(&app, e).y
+}
+
+#[test]
+fn fast_drop_lands_on_floor() {
+    let y = drop(MovementSolver::Depenetration, ContinuousCollision::default());
+    assert!(y > 0.0 && y < CHARACTER_HIGHT + CHARACTER_RADIUS * 2.0, "y: {y}");
+}
+
+#[test]
+fn fast_drop_lands_on_floor_with_collide_and_slide() {
+    let y = drop(MovementSolver::CollideAndSlide, ContinuousCollision::default());
+    assert!(y > 0.0 && y < CHARACTER_HIGHT + CHARACTER_RADIUS * 2.0, "y: {y}");
+}
+
+// guards the test setup, without sweeping the floor is missed
+#[test]
+fn fast_drop_tunnels_without_continuous_collision() {
+    let y = drop(MovementSolver::Depenetration, ContinuousCollision {
+        enabled: false,
+        ..default()
+    });
+    assert!(y < 0.0, "y: {y}");
+}
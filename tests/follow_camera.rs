@@ -0,0 +1,64 @@
+mod common;
+
+use bevy::{prelude::*, input::InputPlugin};
+use bevy_netcharacon_dev::{
+    camera::*,
+    game_client::LocalCharacter,
+    input_mapping::InputMappingPlugin,
+    level::*
+};
+use common::*;
+
+const UPDATES: u32 = 8;
+// behind the default camera, which looks towards -z
+const WALL_DISTANCE: f32 = 3.0;
+
+// local character at the origin facing -z, level spawns what is around it
+fn camera_app(level: impl FnOnce(&mut World)) -> App {
+    let mut app = game_app();
+    app.add_plugins((InputPlugin, InputMappingPlugin, FollowCameraPlugin));
+    app.world.spawn((TransformBundle::default(), LocalCharacter));
+    level(&mut app.world);
+    app.finish();
+    app.cleanup();
+    app
+}
+
+fn boom_length(level: impl FnOnce(&mut World)) -> f32 {
+    let mut app = camera_app(level);
+    for _ in 0..UPDATES {
+        app.update();
+    }
+    let camera = app.world.query_filtered::<&Transform, With<FollowCamera>>()
+    .single(&app.world)
+    .translation;
+    camera.distance(Vec3::Y * CAMERA_PIVOT_HEIGHT)
+}
+
+#[test]
+fn wall_pulls_boom_in() {
+    let length = boom_length(|world| {
+        spawn_box(
+            world,
+            Transform::from_xyz(0.0, 0.0, WALL_DISTANCE + 0.5),
+            Vec3::new(20.0, 20.0, 1.0)
+        );
+    });
+    assert!(length < WALL_DISTANCE, "boom length: {length}");
+}
+
+// the pivot of a swimming character is inside the water
+#[test]
+fn liquid_and_ladder_volumes_do_not_block_boom() {
+    let length = boom_length(|world| {
+        world.spawn((
+            TransformBundle::from_transform(Transform::from_xyz(0.0, CAMERA_PIVOT_HEIGHT, 0.0)),
+            water_volume()
+        ));
+        world.spawn((
+            TransformBundle::from_transform(Transform::from_xyz(0.0, CAMERA_PIVOT_HEIGHT, WALL_DISTANCE)),
+            ladder_volume()
+        ));
+    });
+    assert!((length - CAMERA_BOOM_LENGTH).abs() < 0.01, "boom length: {length}");
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_netcharacon_dev::character_controller::*;
+use common::*;
+
+const FLOOR_LENGTH: f32 = 20.0;
+const FLOOR_THICKNESS: f32 = 1.0;
+// deeper than the grounded distance, within the snap distance
+const SNAP_STEP: f32 = 0.3;
+const FALL_STEP: f32 = 1.0;
+const WALK_TICKS: u32 = 90;
+
+// upper floor ends at x = 0, its top is at y = 0, lower floor continues to +x
+fn step_down_app(step: f32) -> App {
+    physics_app(|world| {
+        for (x, top) in [(-FLOOR_LENGTH * 0.5, 0.0), (FLOOR_LENGTH * 0.5, -step)] {
+            spawn_box(
+                world,
+                Transform::from_xyz(x, top - FLOOR_THICKNESS * 0.5, 0.0),
+                Vec3::new(FLOOR_LENGTH, FLOOR_THICKNESS, FLOOR_LENGTH)
+            );
+        }
+    })
+}
+
+fn spawn_on_upper_floor(app: &mut App) -> Entity {
+    let e = spawn_character(app, standing_at(-2.0, 0.0, 0.0)).id();
+    wait_for::<Grounded>(app, e);
+    e
+}
+
+// walks towards +x, returns the number of ticks without Grounded
+fn walk_off_step(app: &mut App, e: Entity) -> u32 {
+    let mut airborne = 0;
+    for _ in 0..WALK_TICKS {
+        send_action(app, e, ControllerAction::Move(Vec2::X));
+        app.update();
+        if app.world.get::<Grounded>(e).is_none() {
+            airborne += 1;
+        }
+    }
+    airborne
+}
+
+#[test]
+fn stays_grounded_walking_off_step() {
+    let mut app = step_down_app(SNAP_STEP);
+    let e = spawn_on_upper_floor(&mut app);
+    let start = position(&app, e);
+    let airborne = walk_off_step(&mut app, e);
+    let end = position(&app, e);
+    assert!(end.x > 1.0, "start: {start}, end: {end}");
+    assert!((start.y - end.y - SNAP_STEP).abs() < 0.05, "start: {start}, end: {end}");
+    assert_eq!(airborne, 0, "airborne for {airborne} ticks");
+}
+
+#[test]
+fn falls_off_step_deeper_than_snap_distance() {
+    let mut app = step_down_app(FALL_STEP);
+    let e = spawn_on_upper_floor(&mut app);
+    let start = position(&app, e);
+    let airborne = walk_off_step(&mut app, e);
+    let end = position(&app, e);
+    assert!(end.x > 1.0, "start: {start}, end: {end}");
+    assert!((start.y - end.y - FALL_STEP).abs() < 0.05, "start: {start}, end: {end}");
+    assert!(airborne > 0);
+    assert!(app.world.get::<Grounded>(e).is_some());
+}
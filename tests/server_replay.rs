@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use bevy_replicon::prelude::*;
+use bevy_netcharacon_dev::{
+    *,
+    game_server::*,
+    server_recorder::*,
+    admin_console::{TeleportCharacter, SetTickRate, SetPaused}
+};
+
+const SESSION_TICKS: u32 = 256;
+
+fn server_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+    .add_plugins(RepliconPlugins.build().disable::<ClientPlugin>())
+    .add_plugins((
+        GameCommonPlugin,
+        GameServerPlugin
+    ));
+    app
+}
+
+fn scripted_action(tick: u32, client: u64) -> NetworkAction {
+    let phase = (tick / 32 + client as u32) % 4;
+    let linear = match phase {
+        0 => Vec2::new(0.0, 1.0),
+        1 => Vec2::new(0.7, 0.3),
+        2 => Vec2::ZERO,
+        _ => Vec2::new(-1.0, -0.5)
+    };
+    NetworkAction {
+        linear,
+        angular: Vec2::new(tick as f32 * 0.1, 0.0),
+        jump: tick % 50 == client as u32,
+        ..default()
+    }
+}
+
+#[test]
+fn replay_reproduces_recorded_trajectories() {
+    let path = std::env::temp_dir().join(format!(
+        "netcharacon_replay_{}.rec",
+        std::process::id()
+    ));
+
+    let mut live = server_app();
+    live.add_plugins(ServerRecorderPlugin)
+    .insert_resource(ServerRecorder::create(&path).unwrap())
+    .insert_resource(TimeUpdateStrategy::ManualDuration(replay_time_step()))
+    .init_resource::<Trajectories>()
+    .add_systems(Last, collect_trajectories);
+    live.finish();
+    live.cleanup();
+
+    for tick in 0..SESSION_TICKS {
+        let joined = match tick {
+            0 => Some(1),
+            40 => Some(2),
+            _ => None
+        };
+        if let Some(client) = joined {
+            live.world.send_event(ServerEvent::ClientConnected {
+                client_id: ClientId::new(client)
+            });
+        }
+        if tick == 200 {
+            live.world.send_event(ServerEvent::ClientDisconnected {
+                client_id: ClientId::new(2),
+                reason: "test".to_string()
+            });
+        }
+        for client in [1, 2] {
+            if (client == 2 && tick < 40) || tick % 3 == 0 {
+                continue;
+            }
+            live.world.send_event(FromClient {
+                client_id: ClientId::new(client),
+                event: scripted_action(tick, client)
+            });
+        }
+        live.update();
+    }
+    live.world.remove_resource::<ServerRecorder>();
+
+    let mut replay = server_app();
+    replay.add_plugins(ServerReplayPlugin)
+    .insert_resource(ServerReplay::open(&path).unwrap());
+    replay.finish();
+    replay.cleanup();
+    for _ in 0..SESSION_TICKS {
+        replay.update();
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    let recorded = live.world.resource::<Trajectories>();
+    let replayed = replay.world.resource::<Trajectories>();
+    assert_eq!(recorded.len(), 2);
+    assert_eq!(
+        recorded.keys().collect::<Vec<_>>(),
+        replayed.keys().collect::<Vec<_>>()
+    );
+    for (client_id, samples) in recorded.iter() {
+        let recorded_bits = samples.iter()
+        .map(TrajectorySample::to_bits)
+        .collect::<Vec<_>>();
+        let replayed_bits = replayed[client_id].iter()
+        .map(TrajectorySample::to_bits)
+        .collect::<Vec<_>>();
+        assert_eq!(recorded_bits, replayed_bits, "client {client_id:?} diverged");
+    }
+
+    // spawn point is not at origin, compare with first sample
+    let samples = &recorded[&ClientId::new(1)];
+    let start = samples[0].translation;
+    let moved = samples.iter()
+    .any(|s| s.translation.x != start.x || s.translation.z != start.z);
+    assert!(moved);
+}
+
+// last sample of every tick, frames without a tick repeat the previous one
+fn samples_by_tick(samples: &[TrajectorySample]) -> BTreeMap<u32, [u32; 5]> {
+    samples.iter()
+    .map(|s| (s.tick, s.to_bits()))
+    .collect()
+}
+
+#[test]
+fn replay_reproduces_admin_commands() {
+    let path = std::env::temp_dir().join(format!(
+        "netcharacon_replay_admin_{}.rec",
+        std::process::id()
+    ));
+
+    // live frames keep the default step, so tick rate changes
+    // give frames with no tick or two ticks
+    let mut live = server_app();
+    live.add_plugins(ServerRecorderPlugin)
+    .insert_resource(ServerRecorder::create(&path).unwrap())
+    .insert_resource(TimeUpdateStrategy::ManualDuration(replay_time_step()))
+    .init_resource::<Trajectories>()
+    .add_systems(Last, collect_trajectories);
+    live.finish();
+    live.cleanup();
+
+    let client_id = ClientId::new(1);
+    for frame in 0..SESSION_TICKS {
+        match frame {
+            0 => {
+                live.world.send_event(ServerEvent::ClientConnected { client_id });
+            }
+            60 => {
+                live.world.send_event(TeleportCharacter {
+                    client_id,
+                    position: Vec3::new(3.0, 6.0, -2.0)
+                });
+            }
+            80 => {
+                live.world.send_event(SetTickRate(32.0));
+            }
+            120 => {
+                live.world.send_event(SetPaused(true));
+            }
+            130 => {
+                live.world.send_event(SetPaused(false));
+            }
+            160 => {
+                live.world.send_event(SetTickRate(128.0));
+            }
+            _ => ()
+        }
+        live.world.send_event(FromClient {
+            client_id,
+            event: scripted_action(frame, 1)
+        });
+        live.update();
+    }
+    live.world.remove_resource::<ServerRecorder>();
+
+    let mut replay = server_app();
+    replay.add_plugins(ServerReplayPlugin)
+    .insert_resource(ServerReplay::open(&path).unwrap());
+    replay.finish();
+    replay.cleanup();
+    while !replay.world.resource::<ServerReplay>()
+    .is_finished(replay.world.resource::<SimulationTick>()) {
+        replay.update();
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    let recorded = samples_by_tick(&live.world.resource::<Trajectories>()[&client_id]);
+    let replayed = samples_by_tick(&replay.world.resource::<Trajectories>()[&client_id]);
+    // the last frame may run ticks past the last recorded entry
+    let last_tick = replay.world.resource::<ServerReplay>().last_tick();
+    for (tick, bits) in recorded.range(..=last_tick) {
+        assert_eq!(Some(bits), replayed.get(tick), "diverged at tick {tick}");
+    }
+
+    // teleport landed and the rate change left frames without a tick
+    assert!(recorded.values().any(|bits| f32::from_bits(bits[3]) < -1.0));
+    assert!((recorded.len() as u32) < SESSION_TICKS);
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use std::f32::consts::PI;
+use bevy::prelude::*;
+use bevy_netcharacon_dev::{
+    *,
+    character_controller::*
+};
+use common::*;
+
+const MAX_SLOPE_ANGLE: f32 = PI / 6.0;
+const STEEP_ANGLE: f32 = PI / 4.0;
+const WALKABLE_ANGLE: f32 = PI / 12.0;
+const SLIDE_TICKS: u32 = 60;
+const RAMP_LENGTH: f32 = 40.0;
+const RAMP_THICKNESS: f32 = 1.0;
+
+// ramp goes down towards +x, its top surface passes through the origin
+fn slope_app(angle: f32) -> App {
+    let rotation = Quat::from_rotation_z(-angle);
+    physics_app(|world| {
+        spawn_box(
+            world,
+            Transform::from_translation(rotation * Vec3::new(0.0, -RAMP_THICKNESS * 0.5, 0.0))
+            .with_rotation(rotation),
+            Vec3::new(RAMP_LENGTH, RAMP_THICKNESS, RAMP_LENGTH)
+        );
+    })
+}
+
+// starts resting on the ramp surface at the origin
+fn spawn_on_ramp(app: &mut App, angle: f32, friction: f32) -> Entity {
+    let normal = Quat::from_rotation_z(-angle) * Vec3::Y;
+    let half_height = CHARACTER_HIGHT * 0.5 + CHARACTER_RADIUS;
+    let center = normal * CHARACTER_RADIUS + Vec3::Y * (half_height - CHARACTER_RADIUS);
+    spawn_character_with(
+        app,
+        Transform::from_translation(center + normal * 0.01),
+        character_bundle()
+        .with_max_slope_angle(MAX_SLOPE_ANGLE)
+        .with_steep_slope_friction(friction)
+    ).id()
+}
+
+fn slide(angle: f32, friction: f32) -> (App, Entity, Vec3) {
+    let mut app = slope_app(angle);
+    let e = spawn_on_ramp(&mut app, angle, friction);
+    app.update();
+    let start = position(&app, e);
+    for _ in 0..SLIDE_TICKS {
+        app.update();
+    }
+    (app, e, start)
+}
+
+#[test]
+fn slides_down_steep_slope() {
+    let (app, e, start) = slide(STEEP_ANGLE, 0.2);
+    let end = position(&app, e);
+    assert!(end.x - start.x > 1.0, "start: {start}, end: {end}");
+    assert!(start.y - end.y > 1.0, "start: {start}, end: {end}");
+    assert!(app.world.get::<OnSteepSlope>(e).is_some());
+    assert!(app.world.get::<Grounded>(e).is_none());
+}
+
+#[test]
+fn stays_on_walkable_slope() {
+    let (app, e, start) = slide(WALKABLE_ANGLE, 0.2);
+    let end = position(&app, e);
+    assert!(start.distance(end) < 0.2, "start: {start}, end: {end}");
+    assert!(app.world.get::<Grounded>(e).is_some());
+    assert!(app.world.get::<OnSteepSlope>(e).is_none());
+}
+
+#[test]
+fn friction_slows_sliding() {
+    let (app_low, e_low, start_low) = slide(STEEP_ANGLE, 0.0);
+    let (app_high, e_high, start_high) = slide(STEEP_ANGLE, 0.8);
+    let low = start_low.y - position(&app_low, e_low).y;
+    let high = start_high.y - position(&app_high, e_high).y;
+    assert!(high > 0.0);
+    assert!(low > high * 1.5, "low friction: {low}, high friction: {high}");
+}
+
+#[test]
+fn cannot_jump_on_steep_slope() {
+    let mut app = slope_app(STEEP_ANGLE);
+    let e = spawn_on_ramp(&mut app, STEEP_ANGLE, 0.2);
+    wait_for::<OnSteepSlope>(&mut app, e);
+    let mut last_y = position(&app, e).y;
+    for _ in 0..10 {
+        send_action(&mut app, e, ControllerAction::Jump);
+        app.update();
+        let y = position(&app, e).y;
+        assert!(y <= last_y + 0.01, "jumped from {last_y} to {y}");
+        last_y = y;
+    }
+}
+
+#[test]
+fn input_into_steep_slope_is_removed() {
+    let mut app = slope_app(STEEP_ANGLE);
+    let e = spawn_on_ramp(&mut app, STEEP_ANGLE, 0.2);
+    wait_for::<OnSteepSlope>(&mut app, e);
+    let mut last_x = position(&app, e).x;
+    // uphill is -x
+    for _ in 0..SLIDE_TICKS {
+        send_action(&mut app, e, ControllerAction::Move(Vec2::NEG_X));
+        app.update();
+        let x = position(&app, e).x;
+        assert!(x >= last_x - 0.01, "moved uphill from {last_x} to {x}");
+        last_x = x;
+    }
+    // sideways input is kept
+    for _ in 0..SLIDE_TICKS {
+        send_action(&mut app, e, ControllerAction::Move(Vec2::Y));
+        app.update();
+    }
+    assert!(position(&app, e).z < -0.5);
+}
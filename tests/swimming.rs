@@ -0,0 +1,103 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::*;
+use bevy_netcharacon_dev::{
+    character_controller::*,
+    level::*
+};
+use common::*;
+
+const POOL_FLOOR: f32 = 20.0;
+const FLOAT_TICKS: u32 = 240;
+const JUMP_TICKS: u32 = 60;
+
+// pool bottom is at y = 0, the surface at WATER_SIZE.y
+fn pool_app() -> App {
+    physics_app(|world| {
+        spawn_box(
+            world,
+            Transform::from_xyz(0.0, -0.5, 0.0),
+            Vec3::new(POOL_FLOOR, 1.0, POOL_FLOOR)
+        );
+        world.spawn((
+            TransformBundle::from_transform(
+                Transform::from_translation(Vec3::Y * WATER_SIZE.y * 0.5)
+            ),
+            water_volume()
+        ));
+    })
+}
+
+// starts standing on the pool bottom
+fn spawn_in_pool(app: &mut App) -> Entity {
+    let e = spawn_character(app, standing_at(0.0, 0.0, 0.0)).id();
+    wait_for::<Swimming>(app, e);
+    e
+}
+
+fn submersion(app: &App, e: Entity) -> Submersion {
+    *app.world.get::<Submersion>(e).unwrap()
+}
+
+fn float_up(app: &mut App) {
+    for _ in 0..FLOAT_TICKS {
+        app.update();
+    }
+}
+
+#[test]
+fn floats_to_surface() {
+    let mut app = pool_app();
+    let e = spawn_in_pool(&mut app);
+    assert!(!submersion(&app, e).is_at_surface());
+    let start = position(&app, e);
+
+    float_up(&mut app);
+    let end = position(&app, e);
+    assert!(end.y - start.y > 1.0, "start: {start}, end: {end}");
+    assert!(app.world.get::<Swimming>(e).is_some());
+    assert!(app.world.get::<Grounded>(e).is_none());
+    let s = submersion(&app, e);
+    assert!(s.is_at_surface(), "submersion: {}", s.fraction);
+    // buoyancy and drag hold it at the surface, it only bobs
+    for _ in 0..FLOAT_TICKS {
+        app.update();
+        let y = position(&app, e).y;
+        assert!((y - end.y).abs() < 0.2, "drifted from {} to {y}", end.y);
+        assert!(submersion(&app, e).is_at_surface());
+        assert!(app.world.get::<Swimming>(e).is_some());
+    }
+}
+
+#[test]
+fn jumps_out_of_water_from_surface() {
+    let mut app = pool_app();
+    let e = spawn_in_pool(&mut app);
+    float_up(&mut app);
+    let surfaced = position(&app, e);
+
+    send_action(&mut app, e, ControllerAction::Jump);
+    let mut peak = surfaced.y;
+    let mut left_water = false;
+    for _ in 0..JUMP_TICKS {
+        app.update();
+        peak = peak.max(position(&app, e).y);
+        if submersion(&app, e).fraction <= 0.0 {
+            left_water = true;
+            assert!(app.world.get::<Swimming>(e).is_none());
+        }
+    }
+    assert!(left_water, "surfaced: {surfaced}, peak: {peak}");
+    assert!(peak - surfaced.y > 1.0, "surfaced: {surfaced}, peak: {peak}");
+}
+
+#[test]
+fn cannot_jump_under_water() {
+    let mut app = pool_app();
+    let e = spawn_in_pool(&mut app);
+    send_action(&mut app, e, ControllerAction::Jump);
+    app.update();
+    let vel = app.world.get::<LinearVelocity>(e).unwrap();
+    assert!(vel.y < 1.0, "velocity: {}", vel.0);
+}